@@ -10,347 +10,466 @@ extern crate rodio;
 
 use std::io::Read;
 use std::io::BufReader;
-use std::collections::HashMap;
 use termion::raw::IntoRawMode;
 use prettytable::Table;
 
-//TODO remove module_inception
-use grid::grid::grid::Grid;
-use grid::tetris::tile::{Tile, SQUARE_OUTLINE};
-use grid::tetris::tetrad::{Tetrad, Queue};
-
-trait Update {
-
-    fn add_tetrad(&mut self, tetrad: &Tetrad);
-    fn remove_tetrad(&mut self, tetrad: &Tetrad);            
-
-    fn add_tile(&mut self, tile: Tile);            
-    fn remove_tile(&mut self, tile: Tile);            
+use grid::tetris::game::{Game, Action, Settings, Snapshot, FRAME, frame_time};
+use grid::tetris::replay::{Replay, Playback};
+use grid::tetris::input::{Decoder, Keyboard, Key};
+use grid::tetris::randomizer::RandomizerKind;
+use grid::tetris::clock::{SystemClock, Timestep};
+use grid::tetris::mode::{Mode, Finish, format_time, SPLIT_LINES};
+use grid::tetris::versus::{Versus, PLAYERS};
+use grid::tetris::garbage;
+use grid::tetris::net::{Host, NetVersus, Spectator, DEFAULT_PORT, HOST};
+use grid::tetris::ai::{Bot, Controller, Weights};
+use grid::tetris::tbp::{self, ExternalBot};
+use grid::tetris::highscore::{HighScores, Entry, format_date};
+use grid::tetris::scene::{Scenes, Scene, Command, MODES, PAUSE_ITEMS};
+use grid::tetris::config::Config;
+use grid::tetris::piece::PieceSet;
+
+// frames the watchable bot waits between placements
+const BOT_DELAY: u64 = 10;
+
+// the screen is redrawn on its own schedule, independent of the 60Hz simulation
+const DISPLAY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(33);
+
+// replay speeds, + and - double and halve within these
+const MIN_SPEED: f64 = 1. / 16.;
+const MAX_SPEED: f64 = 64.;
+
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1).cloned())
+}
 
-    fn valid_tile(&self, tile: Tile) -> bool;            
-    fn full_rows(&self) -> Vec<usize>;
-    fn clear_rows(&mut self, full_rows: Vec<usize>);
+fn has_flag(name: &str) -> bool {
+    std::env::args().any(|arg| arg == name)
 }
 
-impl Update for Grid<Tile> {
+fn read_keys<R: Read>(stdin: &mut R, decoder: &mut Decoder) -> Vec<Key> {
+    let mut buffer = [0; 64];
+    let n_bytes = stdin.read(&mut buffer).unwrap_or(0);
+    decoder.decode(&buffer[..n_bytes])
+}
 
+// held tetrad and stats, the board and the queue side by side
+fn player_table(snapshot: &Snapshot) -> Table {
+    let mut display_queue = String::from("\nnext:\n");
+    display_queue.push_str(&snapshot.render_next());
 
-    fn add_tetrad(&mut self, tetrad: &Tetrad) {
-        for tile in tetrad.tiles.iter() {
-            self.add_tile(*tile);
-        }
+    let mut stats = String::from("\n");
+    stats.push_str(snapshot.mode.name());
+    if let Some(timer) = snapshot.timer {
+        stats.push_str("\n\ntime:\n");
+        stats.push_str(&format_time(timer));
     }
-
-    fn remove_tetrad(&mut self, tetrad: &Tetrad) {
-        for tile in tetrad.tiles.iter() {
-            self.remove_tile(*tile);
+    stats.push_str("\n\nscore:\n");
+    stats.push_str(&snapshot.score.to_string());
+    stats.push_str("\n\nlines:\n");
+    stats.push_str(&snapshot.lines.to_string());
+    if let Some(goal) = snapshot.mode.line_goal() {
+        stats.push_str(&format!("/{}", goal));
+    }
+    stats.push_str("\n\nlevel:\n");
+    stats.push_str(&snapshot.level.to_string());
+    stats.push_str("\n\nlast clear:\n");
+    if let Some(clear) = &snapshot.last_clear {
+        stats.push_str(&clear.name());
+        if clear.combo > 0 {
+            stats.push_str(&format!("\ncombo {}", clear.combo));
         }
     }
 
-    fn add_tile(&mut self, tile: Tile) {
-        self.grid[[tile.row, tile.column]] = tile;
-    }
+    let mut held = String::from("\nheld:\n\n");
+    held.push_str(&snapshot.render_held_tetrad());
 
-    fn remove_tile(&mut self, tile: Tile) {
-        let default_tile = Tile { row: tile.row, column: tile.column, ..Default::default() };
-        self.grid[[tile.row, tile.column]] = default_tile;
+    let mut held_and_stats = table!([held], [stats]);
+    held_and_stats.set_format(*prettytable::format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    let mut display_table = Table::new();
+    if snapshot.mode == Mode::Versus {
+        let meter = garbage::meter(snapshot.garbage, snapshot.board.height);
+        display_table.add_row(
+            row![held_and_stats,
+                meter.display_string(),
+                snapshot.board.display_string(),
+                display_queue]);
+    } else {
+        display_table.add_row(
+            row![held_and_stats,
+                snapshot.board.display_string(),
+                display_queue]);
     }
+    display_table
+}
 
-    fn valid_tile(&self, tile: Tile) -> bool {
-        let row_good = tile.row < self.height;
-        let column_good = tile.column < self.width;
-        let mut location_ocupied = false;
-        if row_good && column_good {
-            location_ocupied = self.grid[[tile.row, tile.column]].empty;
-        }
-        row_good && column_good && location_ocupied
-    }
+fn display(snapshot: &Snapshot) {
+    let display_string = player_table(snapshot).to_string().replace("\n","\n\r");
+    println!("{}[2J", 27 as char);
+    println!("{}", display_string);
+}
 
-    fn full_rows(&self) -> Vec<usize> {
-        let mut full_rows = Vec::new();
-        for row in self.grid.genrows() {
-            let full_row = row.into_iter().all(|tile| tile.empty == false);
-            if full_row {
-                full_rows.push(row[0].row)
-            }
-        }
-        full_rows
+fn display_versus(snapshots: &[Snapshot]) {
+    let mut display_table = Table::new();
+    display_table.add_row(snapshots.iter().map(|snapshot| cell!(player_table(snapshot))).collect());
+    display_table.set_format(*prettytable::format::consts::FORMAT_CLEAN);
+    let display_string = display_table.to_string().replace("\n","\n\r");
+    println!("{}[2J", 27 as char);
+    println!("{}", display_string);
+}
+
+fn display_results(snapshot: &Snapshot) {
+    let result = match snapshot.finish {
+        Some(finish) => finish.name(),
+        None => "quit",
+    };
+    let mut results = table!(
+        ["mode", snapshot.mode.name()],
+        ["result", result],
+        ["time", format_time(frame_time(snapshot.frames))],
+        ["score", snapshot.score],
+        ["lines", snapshot.lines]);
+    let mut last_split = 0;
+    for (index, split) in snapshot.splits.iter().enumerate() {
+        let lines = (index + 1) * SPLIT_LINES;
+        let split_time = format_time(frame_time(split - last_split));
+        results.add_row(row![format!("{} lines", lines), format!("{}  ({})", format_time(frame_time(*split)), split_time)]);
+        last_split = *split;
     }
 
-    fn clear_rows(&mut self, full_rows: Vec<usize>) {
-    
-        for full_row in full_rows {
-            let _ = self.grid.row_mut(full_row).map_mut(std::mem::take);
-            for row_index in (0..full_row).rev() {
-                let mut bottom_row = self.grid.row_mut(row_index).map_mut(std::mem::take);
-                for tile in bottom_row.iter_mut() {
-                    tile.row += 1;
-                }
-                self.grid.row_mut(row_index + 1).assign(&bottom_row);
-            }
+    let display_string = results.to_string().replace("\n","\n\r");
+    println!("{}[2J", 27 as char);
+    println!("{}", display_string);
+}
+
+// the table with `highlight` marked, sprint times come before scores
+fn high_score_table(scores: &HighScores, highlight: Option<usize>) -> Table {
+    let mut table = if scores.mode.ranked_by_time() {
+        table!(["", "name", "time", "score", "lines", "level", "date"])
+    } else {
+        table!(["", "name", "score", "lines", "level", "time", "date"])
+    };
+    for (rank, entry) in scores.entries.iter().enumerate() {
+        let place = if highlight == Some(rank) { format!("> {}", rank + 1) } else { (rank + 1).to_string() };
+        let time = format_time(frame_time(entry.frames));
+        if scores.mode.ranked_by_time() {
+            table.add_row(row![place, entry.name, time, entry.score, entry.lines, entry.level, format_date(entry.date)]);
+        } else {
+            table.add_row(row![place, entry.name, entry.score, entry.lines, entry.level, time, format_date(entry.date)]);
         }
     }
+    table
 }
 
-trait Move {
-
-    fn move_tetrad(&mut self, grid: &Grid<Tile>, tetrad_mover: Box<dyn Fn(&mut Tetrad)>) -> bool;
+// results, the mode's high scores with the new entry marked, then what to do next
+fn display_game_over(snapshot: &Snapshot, scores: Option<&HighScores>, rank: Option<usize>, notice: &str) {
+    display_results(snapshot);
+    if let Some(scores) = scores.filter(|scores| !scores.entries.is_empty()) {
+        let display_string = high_score_table(scores, rank).to_string().replace("\n","\n\r");
+        println!("{} high scores\r", scores.mode.name());
+        println!("{}", display_string);
+    }
+    if !notice.is_empty() {
+        println!("{}\r", notice);
+    }
+    println!("ENTER title   R restart   Q quit\r");
 }
 
-impl Move for Tetrad {
-
-    fn move_tetrad(&mut self, grid: &Grid<Tile>, tetrad_mover: Box<dyn Fn(&mut Tetrad)>) -> bool {
-        let mut new_tetrad = self.clone();
-        tetrad_mover(&mut new_tetrad);
+fn display_name_entry(snapshot: &Snapshot, rank: usize, name: &str) {
+    display_results(snapshot);
+    println!("new high score: #{}\r", rank + 1);
+    println!("name: {}_\r", name);
+    println!("\rENTER to confirm\r");
+}
 
-        let valid_move = new_tetrad.tiles
-            .iter()
-            .all(|tile| grid.valid_tile(*tile));
-        if valid_move {    
-            tetrad_mover(self);
-        }
-        valid_move
+// the board stays hidden so a pause can't be used to study it
+fn display_paused(selected: usize) {
+    println!("{}[2J", 27 as char);
+    println!("PAUSED\r\n\r");
+    for (index, item) in PAUSE_ITEMS.iter().enumerate() {
+        let marker = if index == selected { ">" } else { " " };
+        println!("{} {}\r", marker, item.name());
     }
+    println!("\r\n⇧ ⇩ choose   ENTER select   P resume\r");
 }
 
-struct Tetris {
-    grid: Grid<Tile>,
-    active_tetrad: Tetrad,
-    tetrad_shadow: Tetrad,
-    queue: Queue,
-    held_tetrad: Option<String>,
-    score: usize,
-    lines: usize,
-    level: usize,
+fn display_mode_select(selected: usize) {
+    println!("{}[2J", 27 as char);
+    println!("select a mode\r\n\r");
+    for (index, mode) in MODES.iter().enumerate() {
+        let marker = if index == selected { ">" } else { " " };
+        println!("{} {}\r", marker, mode.name());
+    }
+    println!("\r\n⇧ ⇩ choose   ENTER select   ESC back\r");
 }
 
-impl Tetris {
+fn display_versus_results(versus: &Versus) {
+    let result = match versus.winner() {
+        Some(player) => format!("player {} wins", player + 1),
+        None if versus.finished() => "draw".to_string(),
+        None => "quit".to_string(),
+    };
+    let mut results = table!(["", "player 1", "player 2"]);
+    let games = &versus.players;
+    results.add_row(row!["score", games[0].score, games[1].score]);
+    results.add_row(row!["lines", games[0].lines, games[1].lines]);
+    results.add_row(row!["time", format_time(games[0].elapsed()), format_time(games[1].elapsed())]);
+    let finish = |game: &Game| game.finish.map_or("", Finish::name);
+    results.add_row(row!["result", finish(&games[0]), finish(&games[1])]);
+
+    let display_string = results.to_string().replace("\n","\n\r");
+    println!("{}[2J", 27 as char);
+    println!("{}\r", result);
+    println!("{}", display_string);
+}
 
-    fn update_level(&mut self) {
-        self.level = (self.lines / 10) + 1;
+fn play_music(sink: &rodio::Sink) {
+    if sink.empty() {
+        match std::fs::File::open("tetris.mp3") {
+            Err(_) => (),
+            Ok(file) => sink.append(rodio::Decoder::new(BufReader::new(file)).unwrap()),
+        };
     }
+}
 
-    fn move_active_tetrad(&mut self, tetrad_mover: Box<dyn Fn(&mut Tetrad)>) -> bool { 
-        let mut tetrad = self.active_tetrad.clone();
-        self.grid.remove_tetrad(&self.active_tetrad);
-        let was_moved = tetrad.move_tetrad(&self.grid, tetrad_mover);
-        self.grid.add_tetrad(&tetrad);
-        self.active_tetrad = tetrad;
-        self.update_shadow();
-        was_moved
-    }
+fn play_versus<R: Read>(settings: Settings, config: &Config, stdin: &mut R, decoder: &mut Decoder, sink: &rodio::Sink) {
+    let mut versus = Versus::new(settings);
+    let mut keyboard = Keyboard::new(config.das, config.arr);
+    let mut pending: [Vec<Action>; PLAYERS] = Default::default();
+    let mut simulation = Timestep::new(SystemClock::new(), FRAME);
+    let mut screen = Timestep::new(SystemClock::new(), DISPLAY_INTERVAL);
+    let mut quit = false;
+    while !quit && !versus.finished() {
 
-    fn get_shadow(&self) -> Tetrad {
-        let mut shadow = self.active_tetrad.clone();
-        while shadow.tiles.iter().all(|tile| self.grid.valid_tile(*tile)) {
-            shadow.tiles.iter_mut().for_each(|tile| tile.row += 1);
-        }
-        for tile in shadow.tiles.iter_mut() {
-            //better way to avoid panic?
-            if tile.row > 0 {
-                tile.row -= 1;
+        play_music(sink);
+
+        for key in read_keys(stdin, decoder) {
+            if key == Key::Char('q') {
+                quit = true;
+            }
+            if keyboard.press(key) {
+                if let Some((player, action)) = config.versus_action(key) {
+                    pending[player].push(action);
+                }
             }
-            tile.utf8 = SQUARE_OUTLINE;
-        }
-        shadow
-    }
-
-    fn update_shadow(&mut self) {
-        self.grid.remove_tetrad(&self.tetrad_shadow);
-        let shadow = self.get_shadow();
-        self.grid.add_tetrad(&shadow);
-        self.tetrad_shadow = shadow;
-        self.grid.add_tetrad(&self.active_tetrad);
-    } 
-
-    fn hard_drop(&mut self) { 
-        self.grid.remove_tetrad(&self.active_tetrad);
-        let color = self.active_tetrad.tiles[0].color;
-        let utf8 = self.active_tetrad.tiles[0].utf8;
-        let from_row = &self.active_tetrad.tiles[0].row;
-        let to_row = &self.tetrad_shadow.tiles[0].row;
-        let rows_dropped = to_row - from_row;
-        self.score += rows_dropped * self.level * 2;
-        self.active_tetrad.tiles = self.tetrad_shadow.tiles;
-        for tile in self.active_tetrad.tiles.iter_mut() {
-            tile.color = color;
-            tile.utf8 = utf8;
         }
-    }
 
-    //TODO
-    //better python like function wrapping?
-    fn move_left(&mut self) { 
-        fn move_tetrad_left(tetrad: &mut Tetrad) {
-            //TODO shouldnt need a specific check for move left 
-            let legal = tetrad.tiles.iter().all(|x| x.column > 0);
-            if legal {
-                for tile in tetrad.tiles.iter_mut() {
-                    tile.column -= 1;
+        for _ in 0..simulation.advance() {
+            for key in keyboard.update(FRAME) {
+                match config.versus_action(key) {
+                    Some((player, action)) if action.auto_repeats() => pending[player].push(action),
+                    _ => (),
                 }
-                tetrad.center.1 -= 1.0;
             }
+            versus.step([&pending[0], &pending[1]]);
+            pending.iter_mut().for_each(Vec::clear);
         }
-        let _was_moved = self.move_active_tetrad(Box::new(move_tetrad_left));
-    }
 
-    fn move_right(&mut self) { 
-        fn move_tetrad_right(tetrad: &mut Tetrad) {
-            tetrad.tiles.iter_mut().for_each(|tile| tile.column += 1);
-            tetrad.center.1 += 1.0;
+        if screen.advance() > 0 {
+            let snapshots = [versus.players[0].snapshot_with(config.ghost), versus.players[1].snapshot_with(config.ghost)];
+            display_versus(&snapshots);
         }
-        let _was_moved = self.move_active_tetrad(Box::new(move_tetrad_right));
+        std::thread::sleep(simulation.until_next().min(screen.until_next()));
     }
-    
-    fn move_down(&mut self, score: usize) { 
-        fn move_tetrad_down(tetrad: &mut Tetrad) {
-            tetrad.tiles.iter_mut().for_each(|tile| tile.row += 1);
-            tetrad.center.0 += 1.0;
-        }
-        let was_moved = self.move_active_tetrad(Box::new(move_tetrad_down));
-        if was_moved {
-            self.score += score;
-        }
+    display_versus_results(&versus);
+    if !quit {
+        wait_for_exit(stdin, decoder);
     }
+}
 
-    fn rotate_tetrad(tetrad: &mut Tetrad, rotation_matrix: [[f32; 2]; 2]) {
-        for tile in tetrad.tiles.iter_mut() {
-            let row = tile.row as f32;
-            let center_row = tetrad.center.0 as f32;
-            let column = tile.column as f32;
-            let center_column = tetrad.center.1 as f32;
-            let normalized = ndarray::arr2(
-                &[[row - center_row],[column - center_column]]);
-            let rotation_matrix = ndarray::arr2(&rotation_matrix);
-            let rotated = rotation_matrix.dot(&normalized);
-            let new_row = rotated[[0,0]] + center_row;
-            let new_column = rotated[[1,0]] + center_column;
-            tile.row = new_row as usize;
-            tile.column = new_column as usize;
+fn wait_for_exit<R: Read>(stdin: &mut R, decoder: &mut Decoder) {
+    println!("Press ENTER to exit\r\n");
+    loop {
+        let keys = read_keys(stdin, decoder);
+        if keys.iter().any(|key| *key == Key::Enter || *key == Key::Char('q')) {
+            return
         }
+        std::thread::sleep(std::time::Duration::from_millis(10));
     }
+}
 
-    fn rotate_left(&mut self) {
-        
-        fn rotate_tetrad_left(tetrad: &mut Tetrad) {
-            let rotation_matrix: [[f32; 2]; 2] = [[0.,-1.],[1.,0.]];
-            Tetris::rotate_tetrad(tetrad, rotation_matrix)
+fn host_online<R: Read>(port: u16, settings: Settings, config: &Config, stdin: &mut R, decoder: &mut Decoder, sink: &rodio::Sink) {
+    let mut host = Host::bind(("0.0.0.0", port), settings).expect("could not listen for an opponent");
+    println!("{}[2J", 27 as char);
+    println!("waiting for an opponent on port {}  (Q to cancel)\r", port);
+    while host.opponent.is_none() {
+        if read_keys(stdin, decoder).contains(&Key::Char('q')) {
+            return
         }
-
-        let _was_moved = self.move_active_tetrad(Box::new(rotate_tetrad_left));
+        host.accept().expect("could not accept a connection");
+        std::thread::sleep(std::time::Duration::from_millis(10));
     }
+    play_online(NetVersus::host(host).unwrap(), config, stdin, decoder, sink);
+}
 
-    fn rotate_right(&mut self) {
-        
-        fn rotate_tetrad_right(tetrad: &mut Tetrad) {
-            let rotation_matrix: [[f32; 2]; 2] = [[0.,1.],[-1.,0.]];
-            Tetris::rotate_tetrad(tetrad, rotation_matrix)
-        }
+fn play_online<R: Read>(mut net: NetVersus, config: &Config, stdin: &mut R, decoder: &mut Decoder, sink: &rodio::Sink) {
+    let mut keyboard = Keyboard::new(config.das, config.arr);
+    let mut pending = Vec::new();
+    let mut simulation = Timestep::new(SystemClock::new(), FRAME);
+    let mut screen = Timestep::new(SystemClock::new(), DISPLAY_INTERVAL);
+    let mut quit = false;
+    let mut error = None;
+    while !quit && !net.finished() {
 
-        let _was_moved = self.move_active_tetrad(Box::new(rotate_tetrad_right));
-    }
+        play_music(sink);
 
-
-    //TODO remove mut?
-    //better way than double reverse()?
-    fn display(&mut self) {
-        let mut display_queue = String::from("\nnext:\n");
-        self.queue.tetrads.reverse();
-        for tetrad in &self.queue.tetrads[..6] {
-            display_queue.push_str(&tetrad.render);
-            display_queue.push_str("\n");
+        for key in read_keys(stdin, decoder) {
+            if key == Key::Char('q') {
+                quit = true;
+            }
+            if keyboard.press(key) {
+                pending.extend(config.bindings.action(key));
+            }
         }
-        self.queue.tetrads.reverse();
-
-        let mut stats = String::from("\nscore:\n");
-        stats.push_str(&self.score.to_string());
-        stats.push_str("\n\nlines:\n");
-        stats.push_str(&self.lines.to_string());
-        stats.push_str("\n\nlevel:\n");
-        stats.push_str(&self.level.to_string());
-
-        let mut held = String::from("\nheld:\n");
-        held.push_str(&self.render_held_tetrad());
 
-        let mut held_and_stats = table!([held], [stats]);
-        held_and_stats.set_format(*prettytable::format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
-        let mut display_table = Table::new();
-        display_table.add_row(
-            row![held_and_stats,
-                self.grid.display_string(),
-                display_queue]);
+        for _ in 0..simulation.advance() {
+            for key in keyboard.update(FRAME) {
+                match config.bindings.action(key) {
+                    Some(action) if action.auto_repeats() => pending.push(action),
+                    _ => (),
+                }
+            }
+            if let Err(step_error) = net.step(&pending) {
+                error = Some(step_error);
+                quit = true;
+                break
+            }
+            pending.clear();
+        }
 
-        let display_string = display_table.to_string().replace("\n","\n\r");
-        println!("{}[2J", 27 as char);
-        println!("{}", display_string);
+        if screen.advance() > 0 {
+            let mut snapshots = vec![net.game.snapshot_with(config.ghost), net.opponent.snapshot()];
+            if net.player != HOST {
+                snapshots.reverse();
+            }
+            display_versus(&snapshots);
+        }
+        std::thread::sleep(simulation.until_next().min(screen.until_next()));
+    }
+    match (error, net.winner()) {
+        (Some(error), _) => println!("connection lost: {}\r", error),
+        (None, Some(player)) if player == net.player => println!("you win\r"),
+        (None, Some(_)) => println!("you lose\r"),
+        (None, None) => println!("no winner\r"),
+    }
+    if !quit {
+        wait_for_exit(stdin, decoder);
     }
+}
 
-    fn render_held_tetrad(&self) -> String {
-        match &self.held_tetrad {
-            Some(name) => Tetrad::new_by_name(name).render,
-            None => "        \n\n\n".to_string()
+fn spectate<R: Read>(address: &str, stdin: &mut R, decoder: &mut Decoder) {
+    let mut spectator = Spectator::connect(address).expect("could not join the game");
+    let mut screen = Timestep::new(SystemClock::new(), DISPLAY_INTERVAL);
+    while !spectator.finished() {
+        if read_keys(stdin, decoder).contains(&Key::Char('q')) {
+            return
+        }
+        if let Err(error) = spectator.update() {
+            println!("connection lost: {}\r", error);
+            return
         }
+        if screen.advance() > 0 {
+            let snapshots: Vec<Snapshot> = spectator.players.iter().map(|player| player.snapshot()).collect();
+            display_versus(&snapshots);
+            println!("spectating {}  (Q quit)\r", address);
+        }
+        std::thread::sleep(screen.until_next());
+    }
+    match spectator.players.iter().position(|player| !player.game_over) {
+        Some(player) => println!("player {} wins\r", player + 1),
+        None => println!("no winner\r"),
     }
+    wait_for_exit(stdin, decoder);
+}
 
-    //TODO needs an "if legal" check
-    fn hold(&mut self) {
-        let active_tetrad_name = self.active_tetrad.name.clone();
-        self.grid.remove_tetrad(&self.active_tetrad);
-        match &self.held_tetrad {
-            Some(name) => {
-                self.active_tetrad = Tetrad::new_by_name(&name);
-                self.update_shadow();
-            },
-            None => {
-                self.active_tetrad = self.queue.next_tetrad();
-                self.update_shadow();
-            }
-        }
-        self.held_tetrad = Some(active_tetrad_name);
+fn bot_weights() -> Weights {
+    match arg_value("--weights") {
+        Some(weights) => weights.parse().unwrap(),
+        None => Weights::default(),
     }
 }
 
-fn vecs_match<T: PartialEq>(a: &Vec<T>, b: &Vec<T>) -> bool {
-    let matches = a.iter().zip(b.iter()).filter(|&(a, b)| a == b).count();
-    matches == a.len() && matches == b.len()
+// plays one game per seed without a screen and reports how far the bot got
+fn bench() {
+    let seeds: u64 = arg_value("--seeds").map_or(10, |seeds| seeds.parse().expect("--seeds must be a number"));
+    let max_pieces = arg_value("--pieces").map_or(1000, |pieces| pieces.parse().expect("--pieces must be a number"));
+    let mode: Mode = arg_value("--mode").map_or(Mode::Endless, |mode| mode.parse().unwrap());
+    let bot = Bot::new(bot_weights());
+
+    let mut results = table!(["seed", "lines", "pieces", "score", "result"]);
+    let mut total_lines = 0;
+    for seed in 0..seeds {
+        let started = std::time::Instant::now();
+        let mut game = Game::new(Settings { seed, mode, ..Default::default() });
+        bot.play(&mut game, max_pieces);
+        let result = match game.finish {
+            Some(finish) => finish.name().to_string(),
+            None => format!("{} pieces", max_pieces),
+        };
+        results.add_row(row![seed, game.lines, game.pieces, game.score, result]);
+        total_lines += game.lines;
+        eprintln!("seed {} done in {:.2?}", seed, started.elapsed());
+    }
+    print!("{}", results);
+    println!("{:?}", bot.weights);
+    println!("average lines: {:.1}", total_lines as f64 / seeds.max(1) as f64);
 }
 
-fn on_new_tetrad(tetris: &mut Tetris, score_table: &HashMap<usize, usize>) -> bool {
-    
-    let mut game_live = true;
-    tetris.active_tetrad.tiles.iter_mut().for_each(|tile| tile.empty = false);
-    tetris.grid.add_tetrad(&tetris.active_tetrad);
+fn watch_replay(path: &str, speed: f64, config: &Config) {
+    let mut file = std::fs::File::open(path).expect("could not open the replay");
+    let replay = Replay::read(&mut file).expect("could not read the replay");
+    let mut playback = Playback::new(replay);
 
-    for tile in tetris.active_tetrad.tiles.iter_mut() {
-        tile.empty = false;
-    }
+    let _stdout = std::io::stdout();
+    let _stdout = _stdout.lock().into_raw_mode().unwrap();
+    let mut stdin = termion::async_stdin();
+    let mut decoder = Decoder::new();
 
-    let full_rows = tetris.grid.full_rows();
-    let n_full_rows = full_rows.len();
-    if n_full_rows > 0 {
-        tetris.grid.clear_rows(full_rows);
-        tetris.lines += n_full_rows;
-        tetris.update_level();
-        tetris.score += score_table.get(&n_full_rows).unwrap() * tetris.level;
-    }
+    let mut paused = false;
+    let mut simulation = Timestep::new(SystemClock::new(), FRAME);
+    simulation.speed = speed;
+    let mut screen = Timestep::new(SystemClock::new(), DISPLAY_INTERVAL);
+    loop {
+        let mut redraw = false;
+        for key in read_keys(&mut stdin, &mut decoder) {
+            match key {
+                Key::Char('q') => return,
+                Key::Char(' ') => paused = !paused,
+                Key::Char('.') if paused => playback.step(),
+                Key::Char('+') => simulation.speed = (simulation.speed * 2.).min(MAX_SPEED),
+                Key::Char('-') => simulation.speed = (simulation.speed / 2.).max(MIN_SPEED),
+                _ => continue,
+            }
+            redraw = true;
+        }
 
-    tetris.active_tetrad = tetris.queue.next_tetrad();
-    tetris.tetrad_shadow = tetris.get_shadow();
-    tetris.grid.add_tetrad(&tetris.tetrad_shadow);
+        // ticks that come due while paused are dropped, not caught up on
+        let ticks = simulation.advance();
+        if !paused {
+            for _ in 0..ticks {
+                playback.step();
+            }
+        }
 
-    let valid_move = tetris.active_tetrad.tiles
-        .iter()
-        .all(|tile| tetris.grid.valid_tile(*tile));
-    if valid_move {    
-        tetris.grid.add_tetrad(&tetris.active_tetrad);
-    } else {
-        game_live = false;
+        if screen.advance() > 0 || redraw {
+            display(&playback.game.snapshot_with(config.ghost));
+            let state = match playback.game.finish {
+                Some(finish) => format!("finished, {}", finish.name()),
+                None if playback.finished() => "finished".to_string(),
+                None if paused => "paused".to_string(),
+                None => "playing".to_string(),
+            };
+            println!("replay {}/{} frames  {}x  {}  (SPACE pause  . step  + - speed  Q quit)\r",
+                playback.game.frames, playback.replay.frames, simulation.speed, state);
+        }
+        std::thread::sleep(simulation.until_next().min(screen.until_next()));
     }
-    game_live
 }
 
-fn main() {
-
+fn display_title(mode: Mode, config: &Config) {
     let tetris_text = 
 " _____    _        _   
 |_   _|__| |_ _ __(_)___
@@ -359,149 +478,313 @@ fn main() {
   |_|\\___|\\__|_|  |_|___/
 
 ";
-    let controls_text = 
-"left:          J  ⇦ 
-
-right:         L  ⇨
-
-down:          K  ⇩
-
-hard drop:     I  ⇧  SPACE
-
-rotate left:   D
-
-rotate right:  F
-
-hold:          S
-
-quit:          Q
-";
+    let controls_text = if mode == Mode::Versus {
+        format!("{}quit:          Q\n", config.versus_help())
+    } else {
+        format!("{}pause:         P  ESC\n\nquit:          Q\n", config.bindings.help())
+    };
     let mut greeting = Table::new();
     greeting.add_row(row![tetris_text]);
-    greeting.add_row(row!["   Press ENTER to begin"]);
+    greeting.add_row(row![format!("   Press ENTER to begin {}\n   M change mode   Q quit", mode.name())]);
     greeting.add_row(row![controls_text]);
     greeting.add_row(row![" Add tetris.mp3 for music"]);
-    //TODO better way for frist screen clear?
-    println!("{}", greeting.to_string());
+    if let Ok(scores) = HighScores::load(mode) {
+        if !scores.entries.is_empty() {
+            greeting.add_row(row![format!("   {} high scores\n{}", mode.name(), high_score_table(&scores, None))]);
+        }
+    }
     println!("{}[2J", 27 as char);
-    println!("{}", greeting.to_string());
+    println!("{}", greeting.to_string().replace("\n","\n\r"));
+}
 
+// the settings for a new game, with a fresh seed each time unless --seed fixes it
+// bad arguments end the program before the terminal goes raw
+const ARGUMENTS_CHECKED: &str = "the arguments were checked at startup";
 
+fn exit_with<E: std::fmt::Display>(error: E) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1)
+}
 
-    //TODO move out of main: https://crates.io/crates/phf
-    let mut score_table: HashMap<usize, usize> = HashMap::new();
-    score_table.insert(1, 100);
-    score_table.insert(2, 300);
-    score_table.insert(3, 500);
-    score_table.insert(4, 1000);
+fn game_settings(mode: Mode, config: &Config) -> Result<Settings, String> {
+    let seed = match arg_value("--seed") {
+        Some(seed) => seed.parse().map_err(|_| format!("--seed must be a number, not {}", seed))?,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    };
+    let randomizer: RandomizerKind = match arg_value("--randomizer") {
+        Some(name) => name.parse()?,
+        None => RandomizerKind::SevenBag,
+    };
+    let mut settings = Settings {
+        seed,
+        randomizer,
+        mode,
+        lock_delay: config.lock_delay,
+        spawn_delay: config.spawn_delay,
+        width: config.width,
+        height: config.height,
+        previews: config.previews,
+        pieces: config.pieces.clone(),
+        ..Default::default()
+    };
+    if let Some(path) = arg_value("--gravity") {
+        let data = std::fs::read_to_string(&path)
+            .map_err(|error| format!("could not read the gravity curve {}: {}", path, error))?;
+        settings.gravity = data.parse().map_err(|error| format!("{}: {}", path, error))?;
+    }
+    Ok(settings)
+}
+
+// a new controller for every game, None when a person plays
+fn make_bot() -> Option<Box<dyn Controller>> {
+    if let Some(command) = arg_value("--tbp") {
+        Some(Box::new(ExternalBot::launch(&command).expect("could not start the bot")))
+    } else if has_flag("--bot") {
+        Some(Box::new(Bot::new(bot_weights())))
+    } else {
+        None
+    }
+}
 
-    let width: usize = 10;
-    let height: usize = 24;
-    let mut tiles: Vec<Tile> = Vec::new();
-    for x in 0..width {
-    	for y in 0..height {
-        	tiles.push(Tile::new(x, y))
-		}
+// writes the game to the --record file if one was given, returning what to tell the player
+fn save_replay(replay: &mut Replay, game: &Game) -> String {
+    let path = match arg_value("--record") {
+        Some(path) => path,
+        None => return String::new(),
+    };
+    replay.finish(game.frames);
+    let saved = std::fs::File::create(&path).and_then(|mut file| replay.write(&mut file));
+    match saved {
+        Ok(()) => format!("replay saved to {}", path),
+        Err(error) => format!("could not save the replay: {}", error),
     }
-    let g = Grid::new(width, height, tiles);
+}
 
-    let mut queue = Queue::new();
-    let mut tetris = Tetris { grid: g, 
-        active_tetrad: queue.next_tetrad(),
-        tetrad_shadow: Tetrad::new_l(), //placeholder
-        queue: queue,
-        held_tetrad: None,
-        score: 0,
-        lines: 0,
-        level: 1}; 
+fn main() {
 
-    tetris.update_shadow();
-    tetris.grid.add_tetrad(&tetris.active_tetrad);
+    let path = arg_value("--config");
+    let mut config = Config::load(path.as_ref().map(std::path::Path::new)).expect("could not read the settings");
+    if let Some(path) = arg_value("--piece-set") {
+        let pieces = PieceSet::load(std::path::Path::new(&path)).expect("could not read the piece set");
+        pieces.check_width(config.width).expect("the piece set doesn't fit the board");
+        config.pieces = pieces;
+    }
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("replay") {
+        let path = args.get(2).expect("usage: tetris replay <file> [--speed N]");
+        let speed = match arg_value("--speed") {
+            Some(speed) => speed.parse().ok()
+                .filter(|speed| (MIN_SPEED..=MAX_SPEED).contains(speed))
+                .expect("--speed must be a number from 0.0625 to 64"),
+            None => 1.,
+        };
+        watch_replay(path, speed, &config);
+        return
+    }
+    if args.get(1).map(String::as_str) == Some("bench") {
+        bench();
+        return
+    }
+
+    let mode: Mode = match arg_value("--mode") {
+        Some(name) => name.parse().unwrap_or_else(|error: String| exit_with(error)),
+        None => Mode::Marathon,
+    };
+    // checked before the terminal goes raw, every later game is set up from the same arguments
+    let settings = game_settings(mode, &config).unwrap_or_else(|error| exit_with(error));
+    if arg_value("--tbp").is_some() {
+        tbp::check_settings(&settings).unwrap_or_else(|error| exit_with(format!("--tbp can't play this game: {}", error)));
+    }
+    // a bare --host takes the default port
+    let host_port: Option<u16> = match arg_value("--host") {
+        Some(port) => Some(port.parse().unwrap_or_else(|_| exit_with(format!("--host needs a port number, not {}", port)))),
+        None if has_flag("--host") => Some(DEFAULT_PORT),
+        None => None,
+    };
 
     let _stdout = std::io::stdout();
     let _stdout = _stdout.lock().into_raw_mode().unwrap();
-    let mut input = termion::async_stdin().bytes();
+    let mut stdin = termion::async_stdin();
+    let mut decoder = Decoder::new();
+    let mut keyboard = Keyboard::new(config.das, config.arr);
 
     let device = rodio::default_output_device().unwrap();
     let sink = rodio::Sink::new(&device);
-    sink.set_volume(0.1);
-
-    let mut can_hold = true;
-    let mut game_live = true;
+    sink.set_volume(config.volume);
 
-    loop {
-        match input.next() {
-            Some(Ok(13)) => break, //enter key
-            Some(Ok(b'q')) => {
-                game_live = false;
-                break
-            } 
-            _ => continue,
-        }
+    if let Some(port) = host_port {
+        host_online(port, settings, &config, &mut stdin, &mut decoder, &sink);
+        return
+    }
+    if let Some(address) = arg_value("--join") {
+        let net = NetVersus::join(address.as_str()).expect("could not join the game");
+        play_online(net, &config, &mut stdin, &mut decoder, &sink);
+        return
+    }
+    if let Some(address) = arg_value("--spectate") {
+        spectate(&address, &mut stdin, &mut decoder);
+        return
     }
 
-    while game_live {
-
-        if sink.empty() {
-            match std::fs::File::open("tetris.mp3") {
-                Err(_) => (),
-                Ok(file) => sink.append(rodio::Decoder::new(BufReader::new(file)).unwrap()),
-            };
-        }
-
-        let position_before = tetris.active_tetrad.get_position();
-        tetris.move_down(0);
-        let position_after = tetris.active_tetrad.get_position();
-
-        if vecs_match(&position_before, &position_after) {
-
-            game_live = on_new_tetrad(&mut tetris, &score_table);
-            if !game_live { break }
-            can_hold = true
+    let mut scenes = Scenes::new(mode);
+    let mut game = Game::new(settings);
+    let mut replay = Replay::new(game.settings.clone());
+    let mut bot: Option<Box<dyn Controller>> = None;
+    let mut next_bot_move = 0;
+    // a game the bot had a hand in stays off the table, even after the player takes over
+    let mut bot_played = false;
+    // the table the last game was ranked in, where it landed, and anything else to report
+    let mut scores: Option<HighScores> = None;
+    let mut rank = None;
+    let mut notice = String::new();
+
+    // inputs wait here until the next tick so every action lands on a whole frame
+    let mut pending = Vec::new();
+    let mut simulation = Timestep::new(SystemClock::new(), FRAME);
+    let mut screen = Timestep::new(SystemClock::new(), DISPLAY_INTERVAL);
+    let mut redraw = true;
+    while scenes.scene != Scene::Quit {
+
+        if scenes.scene == Scene::Playing {
+            play_music(&sink);
         }
 
-        let advance_rate = 1000 - tetris.level * 100;
-        let mut next_drop = std::time::Duration::from_millis(advance_rate as u64);
-        let last_drop = std::time::Instant::now();
-
-        tetris.display();
-        loop {
-            let mut hard_dropped = false;
-            let time_elapsed = last_drop.elapsed();
-            if time_elapsed >= next_drop {
-                break;
+        for key in read_keys(&mut stdin, &mut decoder) {
+            let action = config.bindings.action(key);
+            if scenes.scene == Scene::Playing && action.is_some() {
+                if bot.is_none() && keyboard.press(key) {
+                    pending.extend(action);
+                }
+                continue
             }
-            match input.next() {
-                None => continue,
-                Some(Ok(68)) | Some(Ok(b'j')) => tetris.move_left(), //left arrow
-                Some(Ok(66)) | Some(Ok(b'k')) => tetris.move_down(tetris.level), //down arrow
-                Some(Ok(67)) | Some(Ok(b'l')) => tetris.move_right(), //right arrow
-                Some(Ok(b'd')) => tetris.rotate_left(),
-                Some(Ok(b'f')) => tetris.rotate_right(),
-                Some(Ok(b's')) => {
-                    if can_hold {
-                        tetris.hold();
-                        can_hold = false;
+            let in_game = matches!(scenes.scene, Scene::Playing | Scene::Paused { .. });
+            redraw = true;
+            match scenes.press(key) {
+                Some(Command::Start(Mode::Versus)) => {
+                    play_versus(game_settings(Mode::Versus, &config).expect(ARGUMENTS_CHECKED), &config, &mut stdin, &mut decoder, &sink);
+                    scenes.scene = Scene::Title;
+                },
+                Some(Command::Start(mode)) => {
+                    let settings = game_settings(mode, &config).expect(ARGUMENTS_CHECKED);
+                    replay = Replay::new(settings.clone());
+                    game = Game::new(settings);
+                    bot = make_bot();
+                    bot_played = bot.is_some();
+                    next_bot_move = 0;
+                    notice.clear();
+                    pending.clear();
+                    keyboard.release_all();
+                    simulation.skip();
+                    sink.play();
+                },
+                Some(Command::Pause) => sink.pause(),
+                Some(Command::Resume) => {
+                    keyboard.release_all();
+                    simulation.skip();
+                    sink.play();
+                },
+                Some(Command::Record(name)) => {
+                    if let Some(scores) = &mut scores {
+                        rank = scores.insert(Entry::new(&game, &name));
+                        if let Err(error) = scores.save() {
+                            notice = format!("could not save the high scores: {}", error);
+                        }
                     }
                 },
-                //up arrow
-                Some(Ok(65)) | Some(Ok(b'i')) | Some(Ok(b' ')) => {
-                    tetris.hard_drop();
-                    hard_dropped = true;
-                    },
-                Some(Ok(b'q')) => {
-                    game_live = false;
-                    break;
+                Some(Command::Quit) if in_game => {
+                    save_replay(&mut replay, &game);
                 },
-                _ => break
+                Some(Command::Quit) | None => (),
+            }
+        }
+
+        if scenes.scene == Scene::Playing {
+            for _ in 0..simulation.advance() {
+                for key in keyboard.update(FRAME) {
+                    match config.bindings.action(key) {
+                        Some(action) if action.auto_repeats() => pending.push(action),
+                        _ => (),
+                    }
+                }
+                if bot.is_none() && game.spawning() {
+                    // initial hold and rotation from keys still held down
+                    let held = keyboard.repeating().into_iter().filter_map(|key| config.bindings.action(key));
+                    pending.extend(held.filter(|action| action.initial()));
+                }
+                if let Some(controller) = &mut bot {
+                    if game.frames >= next_bot_move && !game.spawning() {
+                        match controller.actions(&game) {
+                            Ok(actions) => pending.extend(actions.into_iter().flatten()),
+                            Err(error) => {
+                                bot = None;
+                                notice = format!("the bot failed ({}), the controls are yours", error);
+                            },
+                        }
+                        next_bot_move = game.frames + BOT_DELAY;
+                    }
+                }
+                for action in pending.iter() {
+                    replay.record(game.frames, *action);
+                }
+                game.step(&pending);
+                pending.clear();
+                if let Some(controller) = &mut bot {
+                    if let Err(error) = controller.stepped(&game) {
+                        bot = None;
+                        notice = format!("the bot failed ({}), the controls are yours", error);
+                    }
+                }
             }
-            if hard_dropped {
-                break;
+
+            if game.game_over {
+                sink.pause();
+                let saved = save_replay(&mut replay, &game);
+                if !saved.is_empty() {
+                    notice = saved;
+                }
+                rank = None;
+                scores = match HighScores::load(game.settings.mode) {
+                    Ok(scores) => Some(scores),
+                    Err(error) => {
+                        notice = format!("could not load the high scores: {}", error);
+                        None
+                    },
+                };
+                // bot games stay off the table
+                let placed = match (&scores, bot_played) {
+                    (Some(scores), false) => scores.rank(&game),
+                    _ => None,
+                };
+                scenes.game_over(placed);
+                redraw = true;
             }
+        } else {
+            // the clock stands still outside of play
+            simulation.skip();
+        }
 
-            tetris.display();
-            next_drop -= time_elapsed;
+        let frame_due = screen.advance() > 0 && scenes.scene == Scene::Playing;
+        if frame_due || redraw {
+            match &scenes.scene {
+                Scene::Title => display_title(scenes.mode, &config),
+                Scene::ModeSelect { selected } => display_mode_select(*selected),
+                Scene::Playing => {
+                    display(&game.snapshot_with(config.ghost));
+                    if !notice.is_empty() {
+                        println!("{}\r", notice);
+                    }
+                },
+                Scene::Paused { selected } => display_paused(*selected),
+                Scene::GameOver => display_game_over(&game.snapshot(), scores.as_ref(), rank, &notice),
+                Scene::HighScoreEntry { rank, name } => display_name_entry(&game.snapshot(), *rank, name),
+                Scene::Quit => (),
+            }
+            redraw = false;
         }
+        std::thread::sleep(simulation.until_next().min(screen.until_next()));
     }
-    println!("GAME OVER\r\n");
 }
@@ -0,0 +1,521 @@
+use std::collections::HashSet;
+use std::io;
+use std::str::FromStr;
+
+use super::board::Board;
+use super::piece::{Piece, Kicks, MAX_PIECE_SIZE};
+use super::srs::Rotation;
+use super::game::{self, Action, Game};
+
+// each feature is multiplied by its weight and summed, the highest total wins
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Weights {
+    pub height: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+    pub lines: f64,
+    pub wells: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights {
+            height: -0.510066,
+            holes: -0.35663,
+            bumpiness: -0.184483,
+            lines: 0.760666,
+            wells: -0.05,
+        }
+    }
+}
+
+// comma separated `feature=weight` pairs, features left out keep their default
+impl FromStr for Weights {
+    type Err = String;
+
+    fn from_str(data: &str) -> Result<Weights, String> {
+        let mut weights = Weights::default();
+        for pair in data.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let mut fields = pair.splitn(2, '=');
+            let name = fields.next().unwrap_or("").trim();
+            let value: f64 = fields.next()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or_else(|| format!("expected `feature=weight`, got `{}`", pair))?;
+            match name {
+                "height" => weights.height = value,
+                "holes" => weights.holes = value,
+                "bumpiness" => weights.bumpiness = value,
+                "lines" => weights.lines = value,
+                "wells" => weights.wells = value,
+                _ => return Err(format!("unknown feature: {}", name)),
+            }
+        }
+        Ok(weights)
+    }
+}
+
+// the stack a placement leaves behind
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Features {
+    // sum of the column heights
+    pub height: usize,
+    // empty cells with a filled cell somewhere above them
+    pub holes: usize,
+    // sum of the height differences between neighbouring columns
+    pub bumpiness: usize,
+    pub lines: usize,
+    // sum of how far each column sits below both of its neighbours, walls count as full
+    pub wells: usize,
+}
+
+impl Features {
+
+    // rows from the top, bit n of a row set when column n is filled
+    pub fn measure(rows: &[u64], width: usize) -> Features {
+        let n_rows = rows.len();
+        let mut heights = vec![0; width];
+        let mut holes = 0;
+        // columns with a filled cell in some row seen so far
+        let mut covered = 0;
+        for (index, row) in rows.iter().enumerate() {
+            let mut tops = row & !covered;
+            while tops != 0 {
+                heights[tops.trailing_zeros() as usize] = n_rows - index;
+                tops &= tops - 1;
+            }
+            holes += (covered & !row).count_ones() as usize;
+            covered |= row;
+        }
+
+        let bumpiness = heights.windows(2)
+            .map(|pair| (pair[0] as isize - pair[1] as isize).unsigned_abs())
+            .sum();
+        let mut wells = 0;
+        for column in 0..width {
+            let left = if column == 0 { n_rows } else { heights[column - 1] };
+            let right = heights.get(column + 1).copied().unwrap_or(n_rows);
+            wells += left.min(right).saturating_sub(heights[column]);
+        }
+        Features { height: heights.iter().sum(), holes, bumpiness, lines: 0, wells }
+    }
+
+    pub fn score(&self, weights: &Weights) -> f64 {
+        weights.height * self.height as f64
+            + weights.holes * self.holes as f64
+            + weights.bumpiness * self.bumpiness as f64
+            + weights.lines * self.lines as f64
+            + weights.wells * self.wells as f64
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Placement {
+    // where the tetrad locks
+    pub tetrad: Piece,
+    pub hold: bool,
+    // every action from the current position to the lock, hold and hard drop included
+    pub actions: Vec<Action>,
+    pub features: Features,
+    pub score: f64,
+}
+
+const MOVES: [Action; 5] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::RotateRight,
+    Action::RotateLeft,
+    Action::SoftDrop,
+];
+
+// a tetrad reduced to bits, for each rotation one mask per row of its rotation box
+#[derive(Clone, Debug)]
+pub struct Shape {
+    pub name: String,
+    // side of the rotation box
+    pub size: usize,
+    pub masks: [Vec<u64>; 4],
+    pub kicks: Kicks,
+}
+
+impl Shape {
+
+    pub fn new(tetrad: &Piece) -> Shape {
+        let size = tetrad.kind.size;
+        let mut masks: [Vec<u64>; 4] = Default::default();
+        let mut rotated = tetrad.clone();
+        for _ in 0..4 {
+            let masks = &mut masks[rotated.rotation.index()];
+            masks.resize(size, 0);
+            for (row, column) in rotated.get_position() {
+                masks[(row - rotated.origin.0) as usize] |= 1 << (column - rotated.origin.1);
+            }
+            rotated = rotated.rotate(true, (0, 0));
+        }
+        Shape { name: tetrad.name.clone(), size, masks, kicks: tetrad.kind.kicks.clone() }
+    }
+}
+
+// where a shape is: its rotation and the top left corner of its rotation box
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub rotation: Rotation,
+    pub row: isize,
+    pub column: isize,
+}
+
+impl Position {
+
+    pub fn of(tetrad: &Piece) -> Position {
+        Position { rotation: tetrad.rotation, row: tetrad.origin.0, column: tetrad.origin.1 }
+    }
+}
+
+// for every rotation and column of a shape's box, the rows it fits on as one mask,
+// so every check during a search is a single bit. bit n is the box on row n - buffer - margin,
+// a box that fits hangs at most margin, one less than its size, cells off the top or the left
+struct Fits {
+    buffer: isize,
+    margin: isize,
+    n_rows: usize,
+    n_columns: usize,
+    free: Vec<u128>,
+}
+
+impl Fits {
+
+    fn new(board: &Board, shape: &Shape) -> Fits {
+        let margin = shape.size - 1;
+        let n_rows = board.rows.len() + margin + 1;
+        let n_columns = board.width + margin + 1;
+        // per column, bit n set when row n - buffer - margin is filled or off the board,
+        // with margin columns of wall on either side
+        let floor = (!0u128).checked_shl((board.rows.len() + margin) as u32).unwrap_or(0);
+        let ceiling = (1u128 << margin) - 1;
+        let mut solid = vec![!0u128; board.width + 2 * margin];
+        for (column, bits) in solid.iter_mut().skip(margin).take(board.width).enumerate() {
+            *bits = floor | ceiling;
+            for (index, row) in board.rows.iter().enumerate() {
+                *bits |= ((row >> column & 1) as u128) << (index + margin);
+            }
+        }
+
+        let mut free = vec![0; 4 * n_columns];
+        for (rotation, masks) in shape.masks.iter().enumerate() {
+            for column in 0..n_columns {
+                let mut blocked = 0;
+                for (offset, mask) in masks.iter().enumerate() {
+                    let mut cells = *mask;
+                    while cells != 0 {
+                        blocked |= solid.get(column + cells.trailing_zeros() as usize).copied().unwrap_or(!0) >> offset;
+                        cells &= cells - 1;
+                    }
+                }
+                free[rotation * n_columns + column] = !blocked;
+            }
+        }
+        Fits { buffer: board.buffer as isize, margin: margin as isize, n_rows, n_columns, free }
+    }
+
+    fn row(&self, position: Position) -> isize {
+        position.row + self.buffer + self.margin
+    }
+
+    fn column(&self, position: Position) -> u128 {
+        self.free[position.rotation.index() * self.n_columns + (position.column + self.margin) as usize]
+    }
+
+    fn fits(&self, position: Position) -> bool {
+        let row = self.row(position);
+        let column = position.column + self.margin;
+        let inside = (0..128).contains(&row) && (0..self.n_columns as isize).contains(&column);
+        inside && self.column(position) >> row & 1 == 1
+    }
+
+    // where a position that fits lands when dropped
+    fn landing(&self, position: Position) -> Position {
+        let free = (self.column(position) >> self.row(position)).trailing_ones();
+        Position { row: position.row + free as isize - 1, ..position }
+    }
+
+    // a dense index for every position that fits
+    fn slot(&self, position: Position) -> usize {
+        let row = self.row(position) as usize;
+        let column = (position.column + self.margin) as usize;
+        (position.rotation.index() * self.n_rows + row) * self.n_columns + column
+    }
+
+    fn n_slots(&self) -> usize {
+        4 * self.n_rows * self.n_columns
+    }
+}
+
+// the same moves the game makes, rotations try the kicks in order
+fn try_move(fits: &Fits, shape: &Shape, position: Position, action: Action) -> Option<Position> {
+    let rotate = |clockwise: bool| {
+        let from = position.rotation;
+        let to = if clockwise { from.clockwise() } else { from.counter_clockwise() };
+        shape.kicks[from.index()][clockwise as usize]
+            .iter()
+            .map(|kick| Position { rotation: to, row: position.row - kick.1, column: position.column + kick.0 })
+            .find(|rotated| fits.fits(*rotated))
+    };
+    let shift = |rows: isize, columns: isize| {
+        let moved = Position { row: position.row + rows, column: position.column + columns, ..position };
+        Some(moved).filter(|moved| fits.fits(*moved))
+    };
+    match action {
+        Action::MoveLeft => shift(0, -1),
+        Action::MoveRight => shift(0, 1),
+        Action::SoftDrop => shift(1, 0),
+        Action::RotateRight => rotate(true),
+        Action::RotateLeft => rotate(false),
+        _ => None,
+    }
+}
+
+// the cells under some masks as (top row, leftmost column, masks moved against both),
+// the same for every position that covers the same cells
+type Footprint = (isize, isize, [u64; MAX_PIECE_SIZE]);
+
+fn footprint(masks: &[u64], row: isize, column: isize) -> Footprint {
+    let top = masks.iter().position(|mask| *mask != 0).unwrap_or(0);
+    let left = masks.iter().fold(0, |all, mask| all | mask).trailing_zeros().min(63);
+    let mut moved = [0; MAX_PIECE_SIZE];
+    for (offset, mask) in masks.iter().skip(top).enumerate() {
+        moved[offset] = mask >> left;
+    }
+    (row + top as isize, column + left as isize, moved)
+}
+
+fn position_footprint(shape: &Shape, position: Position) -> Footprint {
+    footprint(&shape.masks[position.rotation.index()], position.row, position.column)
+}
+
+// None if the cells don't fit in a rotation box
+fn cells_footprint(cells: &[(isize, isize)]) -> Option<Footprint> {
+    let top = cells.iter().map(|cell| cell.0).min()?;
+    let left = cells.iter().map(|cell| cell.1).min()?;
+    let mut masks = [0; MAX_PIECE_SIZE];
+    for (row, column) in cells {
+        let offset = (row - top) as usize;
+        if offset >= masks.len() || column - left >= 64 {
+            return None
+        }
+        masks[offset] |= 1 << (column - left);
+    }
+    Some(footprint(&masks, top, left))
+}
+
+// the tetrad moved to a position the search found
+fn place(tetrad: &Piece, position: Position) -> Piece {
+    let mut placed = tetrad.clone();
+    while placed.rotation != position.rotation {
+        placed = placed.rotate(true, (0, 0));
+    }
+    placed.shift(position.row - placed.origin.0, position.column - placed.origin.1);
+    placed
+}
+
+struct Node {
+    position: Position,
+    // whether the last action was a rotation, only tracked when a search asks for it
+    rotated: bool,
+    parent: usize,
+    action: Action,
+}
+
+// every position reachable from `start`, breadth first so each is reached with the fewest actions
+fn explore(fits: &Fits, shape: &Shape, start: Position, track_rotation: bool) -> Vec<Node> {
+    if !fits.fits(start) {
+        return Vec::new()
+    }
+    let slot = |position: Position, rotated: bool| fits.slot(position) * 2 + rotated as usize;
+    let mut seen = vec![false; fits.n_slots() * 2];
+    let mut nodes = vec![Node { position: start, rotated: false, parent: 0, action: Action::HardDrop }];
+    seen[slot(start, false)] = true;
+
+    let mut next = 0;
+    while next < nodes.len() {
+        let position = nodes[next].position;
+        for action in MOVES.iter() {
+            if let Some(moved) = try_move(fits, shape, position, *action) {
+                let rotated = track_rotation && (*action == Action::RotateLeft || *action == Action::RotateRight);
+                let slot = slot(moved, rotated);
+                if !seen[slot] {
+                    seen[slot] = true;
+                    nodes.push(Node { position: moved, rotated, parent: next, action: *action });
+                }
+            }
+        }
+        next += 1;
+    }
+    nodes
+}
+
+// the actions that lead from the start to a node, ending with a hard drop
+fn path(nodes: &[Node], mut index: usize) -> Vec<Action> {
+    let mut actions = vec![Action::HardDrop];
+    while index != 0 {
+        actions.push(nodes[index].action);
+        index = nodes[index].parent;
+    }
+    actions.reverse();
+    actions
+}
+
+// every distinct spot the shape can lock in with the actions that get it there
+fn landings(board: &Board, shape: &Shape, start: Position) -> Vec<(Position, Vec<Action>)> {
+    let fits = Fits::new(board, shape);
+    let nodes = explore(&fits, shape, start, false);
+    let mut seen = vec![false; fits.n_slots()];
+    // rotations of symmetric tetrads can lock on the same cells
+    let mut locked: HashSet<Footprint> = HashSet::new();
+    let mut landings = Vec::new();
+    for (index, node) in nodes.iter().enumerate() {
+        let landed = fits.landing(node.position);
+        let slot = fits.slot(landed);
+        if !seen[slot] {
+            seen[slot] = true;
+            if locked.insert(position_footprint(shape, landed)) {
+                landings.push((landed, path(&nodes, index)));
+            }
+        }
+    }
+    landings
+}
+
+// every spot the tetrad can lock in, searched breadth first so each comes with
+// the fewest actions that reach it, tucks and spins under overhangs included
+pub fn reachable(board: &Board, tetrad: &Piece) -> Vec<(Piece, Vec<Action>)> {
+    landings(board, &Shape::new(tetrad), Position::of(tetrad))
+        .into_iter()
+        .map(|(position, actions)| (place(tetrad, position), actions))
+        .collect()
+}
+
+// the fewest actions that lock the tetrad on exactly these cells, a spin has to
+// end on a rotation. None if the cells can't be reached
+pub fn path_to(board: &Board, tetrad: &Piece, target: &[(isize, isize)], spin: bool) -> Option<Vec<Action>> {
+    let target = cells_footprint(target)?;
+    let shape = Shape::new(tetrad);
+    let fits = Fits::new(board, &shape);
+    let nodes = explore(&fits, &shape, Position::of(tetrad), true);
+    let rests = |position: Position| !fits.fits(Position { row: position.row + 1, ..position });
+    nodes.iter()
+        .position(|node| {
+            position_footprint(&shape, node.position) == target && rests(node.position) && (node.rotated || !spin)
+        })
+        .map(|index| path(&nodes, index))
+}
+
+// locks the shape into a copy of the rows and measures what is left after the clear
+fn evaluate(board: &Board, shape: &Shape, position: Position) -> Features {
+    let mut rows = board.rows.clone();
+    for (offset, mask) in shape.masks[position.rotation.index()].iter().enumerate() {
+        let index = position.row + offset as isize + board.buffer as isize;
+        if *mask != 0 {
+            let column = position.column;
+            rows[index as usize] |= if column >= 0 { mask << column } else { mask >> -column };
+        }
+    }
+    let full = board.full_row();
+    let before = rows.len();
+    rows.retain(|row| *row != full);
+    let lines = before - rows.len();
+    for _ in 0..lines {
+        rows.insert(0, 0);
+    }
+    Features { lines, ..Features::measure(&rows, board.width) }
+}
+
+// anything that decides a game's next placement, built in or an external program
+pub trait Controller {
+
+    // every action up to and including the lock, None when there is nothing to play
+    fn actions(&mut self, game: &Game) -> io::Result<Option<Vec<Action>>>;
+
+    // called after each step so the controller can follow what happened
+    fn stepped(&mut self, _game: &Game) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct Bot {
+    pub weights: Weights,
+}
+
+impl Bot {
+
+    pub fn new(weights: Weights) -> Bot {
+        Bot { weights }
+    }
+
+    // the best placement of the active tetrad, or of the one hold would swap in
+    pub fn plan(&self, game: &Game) -> Option<Placement> {
+        if game.game_over || game.spawning() {
+            return None
+        }
+        let mut best = self.best(&game.board, &game.active_tetrad, false);
+
+        // with nothing held hold swaps in the next tetrad, which has to be in the previews
+        let swapped = match &game.held_tetrad {
+            Some(name) => Some(name.as_str()),
+            None => game.previewed().next().map(|tetrad| tetrad.name.as_str()),
+        };
+        if let (true, Some(name)) = (game.can_hold, swapped) {
+            let spawned = game.settings.pieces.piece(name).map(|tetrad| game::spawn_position(&game.board, tetrad));
+            if let Some((tetrad, true)) = spawned {
+                let held = self.best(&game.board, &tetrad, true);
+                best = match (best, held) {
+                    (Some(best), Some(held)) if held.score > best.score => Some(held),
+                    (None, held) => held,
+                    (best, _) => best,
+                };
+            }
+        }
+        best
+    }
+
+    fn best(&self, board: &Board, tetrad: &Piece, hold: bool) -> Option<Placement> {
+        let shape = Shape::new(tetrad);
+        let mut best: Option<(Position, Vec<Action>, Features, f64)> = None;
+        for (position, actions) in landings(board, &shape, Position::of(tetrad)) {
+            let features = evaluate(board, &shape, position);
+            let score = features.score(&self.weights);
+            let better = match &best {
+                Some((_, _, _, best)) => score > *best,
+                None => true,
+            };
+            if better {
+                best = Some((position, actions, features, score));
+            }
+        }
+        best.map(|(position, mut actions, features, score)| {
+            if hold {
+                actions.insert(0, Action::Hold);
+            }
+            Placement { tetrad: place(tetrad, position), hold, actions, features, score }
+        })
+    }
+
+    // plays until the game ends or `max_pieces` tetrads have locked, with no screen and no clock
+    pub fn play(&self, game: &mut Game, max_pieces: usize) {
+        while !game.game_over && game.pieces < max_pieces {
+            if game.spawning() {
+                game.step(&[]);
+                continue
+            }
+            match self.plan(game) {
+                Some(placement) => game.step(&placement.actions),
+                None => game.step(&[Action::HardDrop]),
+            }
+        }
+    }
+}
+
+impl Controller for Bot {
+
+    fn actions(&mut self, game: &Game) -> io::Result<Option<Vec<Action>>> {
+        Ok(self.plan(game).map(|placement| placement.actions))
+    }
+}
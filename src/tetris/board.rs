@@ -0,0 +1,172 @@
+extern crate ndarray;
+
+use crate::grid::grid::Grid;
+
+use super::tile::Tile;
+use super::piece::Piece;
+
+// rows above the visible field where tetrads spawn, hidden from the renderer
+pub const BUFFER_HEIGHT: usize = 20;
+// the narrowest and shortest board the settings allow
+pub const MIN_SIZE: usize = 4;
+// a row is one u64 with a bit per column
+pub const MAX_WIDTH: usize = 64;
+// the bot keeps a column in one u128 with the buffer and a piece hanging over the top
+pub const MAX_HEIGHT: usize = 100;
+
+// locked tiles only, the falling tetrad is drawn over a view of the board
+// row 0 is the top visible row, the buffer zone uses negative rows
+#[derive(Clone, Debug)]
+pub struct Board {
+    // one mask per row, buffer rows first, bit n set when column n is filled
+    pub rows: Vec<u64>,
+    // what the locked tiles look like, only read to build views
+    pub tiles: Vec<Vec<Tile>>,
+    pub width: usize,
+    pub height: usize,
+    pub buffer: usize,
+}
+
+impl Board {
+
+    pub fn new(width: usize, height: usize) -> Board {
+        assert!(width <= MAX_WIDTH, "boards are at most {} columns wide", MAX_WIDTH);
+        assert!(height <= MAX_HEIGHT, "boards are at most {} rows high", MAX_HEIGHT);
+        let buffer = BUFFER_HEIGHT;
+        Board {
+            rows: vec![0; buffer + height],
+            tiles: vec![vec![Tile::default(); width]; buffer + height],
+            width,
+            height,
+            buffer,
+        }
+    }
+
+    // the mask of a row with every column filled
+    pub fn full_row(&self) -> u64 {
+        if self.width == MAX_WIDTH { !0 } else { (1 << self.width) - 1 }
+    }
+
+    fn index(&self, row: isize) -> Option<usize> {
+        let index = row + self.buffer as isize;
+        if index >= 0 && (index as usize) < self.rows.len() {
+            Some(index as usize)
+        } else {
+            None
+        }
+    }
+
+    // anything outside the board counts as filled
+    pub fn occupied(&self, row: isize, column: isize) -> bool {
+        if column < 0 || column >= self.width as isize {
+            return true
+        }
+        match self.index(row) {
+            Some(index) => self.rows[index] >> column & 1 == 1,
+            None => true,
+        }
+    }
+
+    pub fn valid_tile(&self, tile: Tile) -> bool {
+        !self.occupied(tile.row, tile.column)
+    }
+
+    pub fn valid_tetrad(&self, tetrad: &Piece) -> bool {
+        tetrad.tiles.iter().all(|tile| self.valid_tile(*tile))
+    }
+
+    // how many rows the tetrad can fall before it lands
+    pub fn drop_distance(&self, tetrad: &Piece) -> isize {
+        let mut distance = 0;
+        while tetrad.tiles.iter().all(|tile| !self.occupied(tile.row + distance + 1, tile.column)) {
+            distance += 1;
+        }
+        distance
+    }
+
+    // fills the tetrad's cells, anything off the board is lost
+    pub fn lock(&mut self, tetrad: &Piece) {
+        for tile in tetrad.tiles.iter() {
+            if let (Some(index), true) = (self.index(tile.row), tile.column >= 0 && tile.column < self.width as isize) {
+                self.rows[index] |= 1 << tile.column;
+                self.tiles[index][tile.column as usize] = Tile { empty: false, ..*tile };
+            }
+        }
+    }
+
+    pub fn full_rows(&self) -> Vec<isize> {
+        let full = self.full_row();
+        self.rows.iter()
+            .enumerate()
+            .filter(|(_, row)| **row == full)
+            .map(|(index, _)| index as isize - self.buffer as isize)
+            .collect()
+    }
+
+    // rows have to be in order from the top, as full_rows gives them
+    pub fn clear_rows(&mut self, full_rows: Vec<isize>) {
+        for full_row in full_rows {
+            if let Some(index) = self.index(full_row) {
+                self.rows.remove(index);
+                self.rows.insert(0, 0);
+                self.tiles.remove(index);
+                self.tiles.insert(0, vec![Tile::default(); self.width]);
+            }
+        }
+    }
+
+    // pushes the stack up by `rows` and fills the bottom with garbage tiles
+    // returns false if anything was pushed out of the top of the buffer
+    pub fn add_garbage(&mut self, rows: usize, hole: usize, garbage: Tile) -> bool {
+        let rows = rows.min(self.rows.len());
+        let overflow = self.rows.iter().take(rows).any(|row| *row != 0);
+
+        let mask = self.full_row() & !(1 << hole);
+        let mut tiles = vec![Tile { empty: false, ..garbage }; self.width];
+        tiles[hole] = Tile::default();
+        self.rows.drain(..rows);
+        self.tiles.drain(..rows);
+        for _ in 0..rows {
+            self.rows.push(mask);
+            self.tiles.push(tiles.clone());
+        }
+        !overflow
+    }
+
+    // the board as tiles, buffer included
+    pub fn view(&self) -> Grid<Tile> {
+        self.grid(0)
+    }
+
+    pub fn visible(&self) -> Grid<Tile> {
+        self.grid(self.buffer)
+    }
+
+    // the visible board with tetrads drawn over it in order
+    pub fn visible_with(&self, tetrads: &[&Piece]) -> Grid<Tile> {
+        let mut grid = self.visible();
+        for tile in tetrads.iter().flat_map(|tetrad| tetrad.tiles.iter()) {
+            let inside = tile.row >= 0 && tile.row < self.height as isize
+                && tile.column >= 0 && tile.column < self.width as isize;
+            if inside {
+                grid.grid[[tile.row as usize, tile.column as usize]] = *tile;
+            }
+        }
+        grid
+    }
+
+    fn grid(&self, from: usize) -> Grid<Tile> {
+        let mut tiles = Vec::new();
+        for (index, row) in self.rows.iter().enumerate().skip(from) {
+            for column in 0..self.width {
+                let tile = if row >> column & 1 == 1 { self.tiles[index][column] } else { Tile::default() };
+                tiles.push(Tile { row: index as isize - self.buffer as isize, column: column as isize, ..tile });
+            }
+        }
+        Grid::new(self.width, self.rows.len() - from, tiles)
+    }
+
+    pub fn display_string(&self) -> String {
+        self.visible().display_string()
+    }
+}
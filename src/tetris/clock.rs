@@ -0,0 +1,111 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+// time since the clock started
+pub trait Clock {
+
+    fn now(&self) -> Duration;
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+
+    pub fn new() -> SystemClock {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+// a clock that only moves when told to, clones share the same time
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    pub fn advance(&self, elapsed: Duration) {
+        self.now.set(self.now.get() + elapsed);
+    }
+}
+
+impl Clock for ManualClock {
+
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+// never catch up on more than this many ticks at once, after a stall the game slows down instead
+pub const MAX_TICKS_PER_ADVANCE: usize = 10;
+
+// turns clock time into a whole number of fixed ticks, carrying the remainder
+pub struct Timestep<C: Clock> {
+    pub clock: C,
+    pub tick: Duration,
+    // how fast game time runs compared to the clock
+    pub speed: f64,
+    last: Duration,
+    lag: Duration,
+}
+
+impl<C: Clock> Timestep<C> {
+
+    pub fn new(clock: C, tick: Duration) -> Timestep<C> {
+        let last = clock.now();
+        Timestep { clock, tick, speed: 1., last, lag: Duration::from_millis(0) }
+    }
+
+    // number of ticks due since the last call
+    pub fn advance(&mut self) -> usize {
+        let now = self.clock.now();
+        self.lag += (now - self.last).mul_f64(self.speed);
+        self.last = now;
+
+        let mut ticks = 0;
+        while self.lag >= self.tick {
+            self.lag -= self.tick;
+            ticks += 1;
+        }
+        if ticks > MAX_TICKS_PER_ADVANCE {
+            ticks = MAX_TICKS_PER_ADVANCE;
+        }
+        ticks
+    }
+
+    // lets the time since the last call pass without ticks, for pauses
+    pub fn skip(&mut self) {
+        self.last = self.clock.now();
+    }
+
+    // time left until the next tick is due
+    pub fn until_next(&self) -> Duration {
+        let now = self.clock.now();
+        let lag = self.lag + (now - self.last).mul_f64(self.speed);
+        if lag >= self.tick {
+            Duration::from_millis(0)
+        } else {
+            (self.tick - lag).div_f64(self.speed)
+        }
+    }
+}
@@ -0,0 +1,328 @@
+extern crate serde;
+extern crate toml;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::game::{Action, DEFAULT_LOCK_DELAY, DEFAULT_SPAWN_DELAY, DEFAULT_PREVIEWS, DEFAULT_WIDTH, DEFAULT_HEIGHT};
+use super::input::{Key, DEFAULT_DAS, DEFAULT_ARR};
+use super::tetrad::QUEUE_LENGTH;
+use super::tile::Ghost;
+use super::scene::RESERVED_KEYS;
+use super::piece::PieceSet;
+use super::board::{MIN_SIZE, MAX_WIDTH, MAX_HEIGHT};
+use super::versus::PLAYERS;
+use super::dirs;
+
+pub const SETTINGS_FILE: &str = "settings.toml";
+pub const DEFAULT_VOLUME: f32 = 0.1;
+
+// every bindable action with its name in the settings file and on the help screen, in help screen order
+pub const BINDABLE: [(Action, &str, &str); 7] = [
+    (Action::MoveLeft, "move_left", "left"),
+    (Action::MoveRight, "move_right", "right"),
+    (Action::SoftDrop, "soft_drop", "down"),
+    (Action::HardDrop, "hard_drop", "hard drop"),
+    (Action::RotateLeft, "rotate_left", "rotate left"),
+    (Action::RotateRight, "rotate_right", "rotate right"),
+    (Action::Hold, "hold", "hold"),
+];
+
+// the keys for each action, in BINDABLE order
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings {
+    pub keys: Vec<Vec<Key>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings {
+            keys: vec![
+                vec![Key::Char('j'), Key::Left],
+                vec![Key::Char('l'), Key::Right],
+                vec![Key::Char('k'), Key::Down],
+                vec![Key::Char('i'), Key::Up, Key::Char(' ')],
+                vec![Key::Char('d')],
+                vec![Key::Char('f')],
+                vec![Key::Char('s')],
+            ],
+        }
+    }
+}
+
+impl Bindings {
+
+    // two players on one keyboard, the first on the left half and the second on the arrows
+    pub fn versus(player: usize) -> Bindings {
+        let keys = if player == 0 {
+            ['a', 'd', 's', 'w', 'c', 'v', 'x'].iter().map(|character| vec![Key::Char(*character)]).collect()
+        } else {
+            vec![
+                vec![Key::Left],
+                vec![Key::Right],
+                vec![Key::Down],
+                vec![Key::Up],
+                vec![Key::Char(',')],
+                vec![Key::Char('.')],
+                vec![Key::Char('/')],
+            ]
+        };
+        Bindings { keys }
+    }
+
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.keys.iter()
+            .position(|keys| keys.contains(&key))
+            .map(|index| BINDABLE[index].0)
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        match BINDABLE.iter().position(|(bound, _, _)| *bound == action) {
+            Some(index) => &self.keys[index],
+            None => &[],
+        }
+    }
+
+    // a key can only do one thing, and never what the scenes use it for
+    fn check(&self) -> Result<(), String> {
+        let mut seen: HashMap<Key, &str> = HashMap::new();
+        for (keys, (_, name, _)) in self.keys.iter().zip(BINDABLE.iter()) {
+            for key in keys {
+                if RESERVED_KEYS.contains(key) {
+                    return Err(format!("{} is reserved and can't be bound to {}", key.label(), name))
+                }
+                if let Some(other) = seen.insert(*key, name) {
+                    return Err(format!("{} is bound to both {} and {}", key.label(), other, name))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // the controls part of the help screen, one action per line
+    pub fn help(&self) -> String {
+        let mut help = String::new();
+        for (keys, (_, _, label)) in self.keys.iter().zip(BINDABLE.iter()) {
+            help.push_str(&format!("{:<15}{}\n\n", format!("{}:", label), key_labels(keys)));
+        }
+        help
+    }
+
+    // an action listed in the file loses its default keys
+    fn bind(&mut self, keys: HashMap<String, Vec<String>>) -> Result<(), String> {
+        for (name, keys) in keys {
+            let index = BINDABLE.iter()
+                .position(|(_, known, _)| *known == name)
+                .ok_or_else(|| format!("unknown action: {}", name))?;
+            self.keys[index] = keys.iter()
+                .map(|key| key.parse())
+                .collect::<Result<Vec<Key>, String>>()?;
+        }
+        Ok(())
+    }
+}
+
+fn key_labels(keys: &[Key]) -> String {
+    keys.iter().map(|key| key.label()).collect::<Vec<String>>().join("  ")
+}
+
+// player preferences, everything missing from the settings file keeps its default
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub bindings: Bindings,
+    // one set of keys per player in local versus
+    pub versus: [Bindings; PLAYERS],
+    pub das: Duration,
+    pub arr: Duration,
+    pub lock_delay: Duration,
+    pub spawn_delay: Duration,
+    pub width: usize,
+    // the visible height
+    pub height: usize,
+    // upcoming tetrads shown, at most QUEUE_LENGTH
+    pub previews: usize,
+    pub ghost: Ghost,
+    // 0 is silent, 1 is full volume
+    pub volume: f32,
+    pub pieces: PieceSet,
+    // where the settings were read from, None if they are the defaults
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            bindings: Bindings::default(),
+            versus: [Bindings::versus(0), Bindings::versus(1)],
+            das: DEFAULT_DAS,
+            arr: DEFAULT_ARR,
+            lock_delay: DEFAULT_LOCK_DELAY,
+            spawn_delay: DEFAULT_SPAWN_DELAY,
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            previews: DEFAULT_PREVIEWS,
+            ghost: Ghost::Outline,
+            volume: DEFAULT_VOLUME,
+            pieces: PieceSet::default(),
+            path: None,
+        }
+    }
+}
+
+// the file as written, times in milliseconds and keys by name
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SettingsFile {
+    das: Option<u64>,
+    arr: Option<u64>,
+    lock_delay: Option<u64>,
+    spawn_delay: Option<u64>,
+    width: Option<usize>,
+    height: Option<usize>,
+    previews: Option<usize>,
+    ghost: Option<String>,
+    volume: Option<f32>,
+    // a piece set file, relative to the settings file
+    piece_set: Option<String>,
+    keys: Option<HashMap<String, Vec<String>>>,
+    versus: Option<VersusKeysFile>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct VersusKeysFile {
+    player1: Option<HashMap<String, Vec<String>>>,
+    player2: Option<HashMap<String, Vec<String>>>,
+}
+
+// the per user config directory: $XDG_CONFIG_HOME or ~/.config,
+// ~/Library/Application Support on macos and %APPDATA% on windows
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::user_dir("XDG_CONFIG_HOME", &[".config"])
+}
+
+impl Config {
+
+    // the given file, or the one in the config directory which may be missing
+    pub fn load(path: Option<&Path>) -> io::Result<Config> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match config_dir() {
+                Some(dir) => (dir.join(SETTINGS_FILE), false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == ErrorKind::NotFound && !required => return Ok(Config::default()),
+            Err(error) => return Err(error),
+        };
+        let config = Config::parse(&data, path.parent())
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), error)))?;
+        Ok(Config { path: Some(path), ..config })
+    }
+
+    // relative paths in the file start from `dir`
+    fn parse(data: &str, dir: Option<&Path>) -> Result<Config, String> {
+        let file: SettingsFile = toml::from_str(data).map_err(|error| error.to_string())?;
+        let mut config = Config::default();
+        if let Some(das) = file.das {
+            config.das = Duration::from_millis(das);
+        }
+        if let Some(arr) = file.arr {
+            config.arr = Duration::from_millis(arr);
+        }
+        if let Some(lock_delay) = file.lock_delay {
+            config.lock_delay = Duration::from_millis(lock_delay);
+        }
+        if let Some(spawn_delay) = file.spawn_delay {
+            config.spawn_delay = Duration::from_millis(spawn_delay);
+        }
+        if let Some(width) = file.width {
+            if !(MIN_SIZE..=MAX_WIDTH).contains(&width) {
+                return Err(format!("width must be between {} and {}, not {}", MIN_SIZE, MAX_WIDTH, width))
+            }
+            config.width = width;
+        }
+        if let Some(height) = file.height {
+            if !(MIN_SIZE..=MAX_HEIGHT).contains(&height) {
+                return Err(format!("height must be between {} and {}, not {}", MIN_SIZE, MAX_HEIGHT, height))
+            }
+            config.height = height;
+        }
+        if let Some(previews) = file.previews {
+            if previews > QUEUE_LENGTH {
+                return Err(format!("at most {} previews can be shown", QUEUE_LENGTH))
+            }
+            config.previews = previews;
+        }
+        if let Some(ghost) = file.ghost {
+            config.ghost = ghost.parse()?;
+        }
+        if let Some(volume) = file.volume {
+            if !(0. ..=1.).contains(&volume) {
+                return Err(format!("volume must be between 0 and 1, not {}", volume))
+            }
+            config.volume = volume;
+        }
+        if let Some(piece_set) = file.piece_set {
+            let path = dir.map(|dir| dir.join(&piece_set)).unwrap_or_else(|| PathBuf::from(&piece_set));
+            config.pieces = PieceSet::load(&path).map_err(|error| error.to_string())?;
+        }
+        config.pieces.check_width(config.width)?;
+        config.bindings.bind(file.keys.unwrap_or_default())?;
+        config.bindings.check()?;
+        let versus = file.versus.unwrap_or_default();
+        config.versus[0].bind(versus.player1.unwrap_or_default())?;
+        config.versus[1].bind(versus.player2.unwrap_or_default())?;
+        config.check_versus()?;
+        Ok(config)
+    }
+
+    // both players share the keyboard, so no key may belong to both
+    fn check_versus(&self) -> Result<(), String> {
+        for bindings in self.versus.iter() {
+            bindings.check()?;
+        }
+        for key in self.versus[0].keys.iter().flatten() {
+            if self.versus[1].action(*key).is_some() {
+                return Err(format!("{} is bound for both versus players", key.label()))
+            }
+        }
+        Ok(())
+    }
+
+    // the player whose key it is in local versus, and what it does
+    pub fn versus_action(&self, key: Key) -> Option<(usize, Action)> {
+        self.versus
+            .iter()
+            .enumerate()
+            .find_map(|(player, bindings)| bindings.action(key).map(|action| (player, action)))
+    }
+
+    // the versus controls on the help screen, a column per player
+    pub fn versus_help(&self) -> String {
+        let mut help = format!("{:<15}{:<11}{}\n\n", "", "player 1", "player 2");
+        for (index, (_, _, label)) in BINDABLE.iter().enumerate() {
+            let label = format!("{}:", label);
+            let first = key_labels(&self.versus[0].keys[index]);
+            let second = key_labels(&self.versus[1].keys[index]);
+            help.push_str(&format!("{:<15}{:<11}{}\n\n", label, first, second));
+        }
+        help
+    }
+}
+
+impl FromStr for Config {
+    type Err = String;
+
+    fn from_str(data: &str) -> Result<Config, String> {
+        Config::parse(data, None)
+    }
+}
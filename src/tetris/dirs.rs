@@ -0,0 +1,19 @@
+use std::env;
+use std::path::PathBuf;
+
+// a per user directory for the game: the XDG variable or its default under the home directory,
+// ~/Library/Application Support on macos and %APPDATA% on windows
+pub fn user_dir(xdg_variable: &str, xdg_default: &[&str]) -> Option<PathBuf> {
+    let home = || env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from);
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env::var_os(xdg_variable)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| xdg_default.iter().fold(home, |dir, part| dir.join(part))))
+    };
+    base.map(|base| base.join("grid-tetris"))
+}
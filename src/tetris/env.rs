@@ -0,0 +1,158 @@
+extern crate ndarray;
+
+use ndarray::Array2;
+
+use super::game::{Action, Game, Settings};
+use super::mode::Finish;
+
+// an agent picks one of these by index, or None to let a frame pass
+pub const ACTIONS: [Action; 7] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::RotateLeft,
+    Action::RotateRight,
+    Action::Hold,
+];
+
+// a tetrad's index in the game's piece set
+pub fn piece_index(game: &Game, name: &str) -> usize {
+    game.settings.pieces.index(name).unwrap_or(0)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Piece {
+    pub kind: usize,
+    // clockwise quarter turns from spawn
+    pub rotation: usize,
+    // top left corner of the rotation box in visible rows, negative in the buffer
+    pub row: isize,
+    pub column: isize,
+}
+
+// everything an agent sees after a step, plain numbers only
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    // visible rows by columns, 1 where a tile is locked, the falling tetrad left out
+    pub board: Array2<u8>,
+    pub piece: Piece,
+    pub next: Vec<usize>,
+    pub hold: Option<usize>,
+    pub can_hold: bool,
+    // in the spawn delay piece is the tetrad that just locked, only hold and rotations count
+    pub spawning: bool,
+}
+
+impl Observation {
+
+    pub fn new(game: &Game) -> Observation {
+        let board = &game.board;
+        let cells = Array2::from_shape_fn((board.height, board.width), |(row, column)| {
+            (board.rows[board.buffer + row] >> column & 1) as u8
+        });
+
+        let tetrad = &game.active_tetrad;
+        Observation {
+            board: cells,
+            piece: Piece {
+                kind: piece_index(game, &tetrad.name),
+                rotation: tetrad.rotation.index(),
+                row: tetrad.origin.0,
+                column: tetrad.origin.1,
+            },
+            next: game.previewed().map(|tetrad| piece_index(game, &tetrad.name)).collect(),
+            hold: game.held_tetrad.as_ref().map(|name| piece_index(game, name)),
+            can_hold: game.can_hold,
+            spawning: game.spawning(),
+        }
+    }
+}
+
+// turns what happened during a step into a number, called once per step
+pub trait Reward {
+
+    fn reward(&mut self, game: &Game) -> f64;
+
+    // a new game is starting
+    fn reset(&mut self) {}
+}
+
+// points scored since the last step
+#[derive(Clone, Debug, Default)]
+pub struct ScoreReward {
+    last: usize,
+}
+
+impl Reward for ScoreReward {
+
+    fn reward(&mut self, game: &Game) -> f64 {
+        let reward = game.score - self.last;
+        self.last = game.score;
+        reward as f64
+    }
+
+    fn reset(&mut self) {
+        self.last = 0;
+    }
+}
+
+// lines cleared since the last step, with a fixed penalty for topping out
+#[derive(Clone, Debug, Default)]
+pub struct LinesReward {
+    pub top_out: f64,
+    last: usize,
+}
+
+impl LinesReward {
+
+    pub fn new(top_out: f64) -> LinesReward {
+        LinesReward { top_out, last: 0 }
+    }
+}
+
+impl Reward for LinesReward {
+
+    fn reward(&mut self, game: &Game) -> f64 {
+        let lines = game.lines - self.last;
+        self.last = game.lines;
+        let penalty = if matches!(game.finish, Some(Finish::ToppedOut(_))) { self.top_out } else { 0.0 };
+        lines as f64 - penalty
+    }
+
+    fn reset(&mut self) {
+        self.last = 0;
+    }
+}
+
+// a game behind a reset and step interface for training agents, with no screen, sound or clock
+pub struct Env<R: Reward> {
+    pub settings: Settings,
+    pub reward: R,
+    pub game: Game,
+}
+
+impl<R: Reward> Env<R> {
+
+    pub fn new(settings: Settings, reward: R) -> Env<R> {
+        let game = Game::new(settings.clone());
+        Env { settings, reward, game }
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.settings.seed = seed;
+        self.game = Game::new(self.settings.clone());
+        self.reward.reset();
+        Observation::new(&self.game)
+    }
+
+    // plays one frame with the action, or with no input if None
+    pub fn step(&mut self, action: Option<Action>) -> (Observation, f64, bool) {
+        match action {
+            Some(action) => self.game.step(&[action]),
+            None => self.game.step(&[]),
+        }
+        let reward = self.reward.reward(&self.game);
+        (Observation::new(&self.game), reward, self.game.game_over)
+    }
+}
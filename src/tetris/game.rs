@@ -0,0 +1,759 @@
+use std::time::Duration;
+
+use crate::grid::grid::Grid;
+
+use super::tile::{Tile, Ghost, SQUARE_OUTLINE};
+use super::tetrad::{Queue, QUEUE_LENGTH};
+use super::piece::{self, Piece, PieceSet};
+use super::board::{Board, MIN_SIZE, MAX_WIDTH, MAX_HEIGHT};
+use super::srs::Rotation;
+use super::scoring::{Clear, Spin};
+use super::gravity::Gravity;
+use super::randomizer::RandomizerKind;
+use super::mode::{Mode, Finish, TopOut, SPLIT_LINES};
+use super::garbage::{self, GarbageMeter};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateLeft,
+    RotateRight,
+    Hold,
+}
+
+impl Action {
+
+    // actions a held key keeps repeating after the auto shift delay
+    pub fn auto_repeats(self) -> bool {
+        matches!(self, Action::MoveLeft | Action::MoveRight | Action::SoftDrop)
+    }
+
+    // actions kept during the spawn delay and applied as the next tetrad appears
+    pub fn initial(self) -> bool {
+        matches!(self, Action::Hold | Action::RotateLeft | Action::RotateRight)
+    }
+}
+
+trait Move {
+
+    fn move_tetrad(&mut self, board: &Board, tetrad_mover: Box<dyn Fn(&mut Piece)>) -> bool;
+}
+
+impl Move for Piece {
+
+    fn move_tetrad(&mut self, board: &Board, tetrad_mover: Box<dyn Fn(&mut Piece)>) -> bool {
+        let mut new_tetrad = self.clone();
+        tetrad_mover(&mut new_tetrad);
+
+        let valid_move = board.valid_tetrad(&new_tetrad);
+        if valid_move {
+            tetrad_mover(self);
+        }
+        valid_move
+    }
+}
+
+pub const DEFAULT_LOCK_DELAY: Duration = Duration::from_millis(500);
+pub const MAX_LOCK_RESETS: usize = 15;
+// entry delay between a lock and the next spawn, six frames
+pub const DEFAULT_SPAWN_DELAY: Duration = Duration::from_millis(100);
+pub const DEFAULT_PREVIEWS: usize = 6;
+pub const DEFAULT_WIDTH: usize = 10;
+pub const DEFAULT_HEIGHT: usize = 20;
+// the simulation advances in fixed frames so identical inputs give identical games
+pub const FRAME: Duration = Duration::from_nanos(16_666_667);
+
+pub fn frame_time(frames: u64) -> Duration {
+    Duration::from_nanos(FRAME.as_nanos() as u64 * frames)
+}
+
+// tetrads spawn in the two rows above the visible field, their spawn column counted
+// from the middle of the board, and drop one row right away if nothing is in the way
+pub fn spawn_position(board: &Board, mut tetrad: Piece) -> (Piece, bool) {
+    tetrad.shift(-2, board.width as isize / 2);
+    let valid_spawn = board.valid_tetrad(&tetrad);
+    if valid_spawn {
+        let mut dropped = tetrad.clone();
+        dropped.shift(1, 0);
+        if board.valid_tetrad(&dropped) {
+            tetrad = dropped;
+        }
+    }
+    (tetrad, valid_spawn)
+}
+
+// everything besides the inputs that decides how a game plays out
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub width: usize,
+    // the visible height, the board adds a hidden buffer above it
+    pub height: usize,
+    pub seed: u64,
+    pub randomizer: RandomizerKind,
+    pub lock_delay: Duration,
+    pub spawn_delay: Duration,
+    pub gravity: Gravity,
+    pub mode: Mode,
+    pub pieces: PieceSet,
+    // upcoming tetrads the player and bots get to see, at most QUEUE_LENGTH
+    pub previews: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            seed: 0,
+            randomizer: RandomizerKind::SevenBag,
+            lock_delay: DEFAULT_LOCK_DELAY,
+            spawn_delay: DEFAULT_SPAWN_DELAY,
+            gravity: Gravity::guideline(),
+            mode: Mode::Marathon,
+            pieces: PieceSet::default(),
+            previews: DEFAULT_PREVIEWS,
+        }
+    }
+}
+
+impl Settings {
+
+    // settings that came from outside, like a replay, are checked before a game is built from them
+    pub fn check(&self) -> Result<(), String> {
+        if !(MIN_SIZE..=MAX_WIDTH).contains(&self.width) {
+            return Err(format!("width must be between {} and {}, not {}", MIN_SIZE, MAX_WIDTH, self.width))
+        }
+        if !(MIN_SIZE..=MAX_HEIGHT).contains(&self.height) {
+            return Err(format!("height must be between {} and {}, not {}", MIN_SIZE, MAX_HEIGHT, self.height))
+        }
+        if self.previews > QUEUE_LENGTH {
+            return Err(format!("at most {} previews can be shown", QUEUE_LENGTH))
+        }
+        self.gravity.check()?;
+        self.pieces.check_width(self.width)
+    }
+}
+
+// what a renderer needs from a game, copied out so drawing never touches the simulation
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub board: Grid<Tile>,
+    // the previewed tetrads, the next one first
+    pub next: Vec<Piece>,
+    pub held: Option<Piece>,
+    // (rows, columns) of the box previews are drawn in
+    pub preview_size: (usize, usize),
+    pub score: usize,
+    pub lines: usize,
+    pub level: usize,
+    pub last_clear: Option<Clear>,
+    pub frames: u64,
+    pub game_over: bool,
+    pub mode: Mode,
+    pub timer: Option<Duration>,
+    pub splits: Vec<u64>,
+    pub finish: Option<Finish>,
+    // garbage lines waiting to rise
+    pub garbage: usize,
+}
+
+impl Snapshot {
+
+    // the held tetrad in the preview box, the empty box when nothing is held
+    pub fn render_held_tetrad(&self) -> String {
+        piece::preview(self.held.as_ref(), self.preview_size)
+    }
+
+    // the previewed tetrads, each in the preview box after a blank line
+    pub fn render_next(&self) -> String {
+        self.next.iter()
+            .map(|tetrad| format!("\n{}\n", piece::preview(Some(tetrad), self.preview_size)))
+            .collect()
+    }
+}
+
+pub struct Game {
+    pub board: Board,
+    pub active_tetrad: Piece,
+    pub tetrad_shadow: Piece,
+    pub queue: Queue,
+    pub held_tetrad: Option<String>,
+    pub can_hold: bool,
+    pub score: usize,
+    pub lines: usize,
+    pub level: usize,
+    pub game_over: bool,
+    // why the game ended, set together with game_over
+    pub finish: Option<Finish>,
+    pub last_clear: Option<Clear>,
+    pub back_to_back: bool,
+    pub combo: usize,
+    pub settings: Settings,
+    // fixed frames played so far
+    pub frames: u64,
+    // tetrads locked so far
+    pub pieces: usize,
+    // frame on which every SPLIT_LINES lines were reached
+    pub splits: Vec<u64>,
+    pub garbage: GarbageMeter,
+    // attack lines not yet collected by take_attack
+    pub outgoing: usize,
+    // time spent on the stack, None while the tetrad is falling
+    lock_timer: Option<Duration>,
+    lock_resets: usize,
+    // set once the tetrad first lands, from then on every move or rotation uses up a reset
+    touched_down: bool,
+    lowest_row: isize,
+    gravity_timer: Duration,
+    // kick used by the last successful action, None unless it was a rotation
+    last_kick: Option<usize>,
+    // time since the last lock, None once the next tetrad is in play
+    spawn_timer: Option<Duration>,
+    // hold and rotation asked for during the spawn delay
+    initial_hold: bool,
+    initial_rotation: Option<bool>,
+}
+
+impl Game {
+
+    pub fn new(settings: Settings) -> Game {
+        let queue = Queue::new(settings.seed, settings.randomizer.build(), settings.pieces.clone());
+        let placeholder = queue.tetrads[0].clone();
+        let mut game = Game {
+            board: Board::new(settings.width, settings.height),
+            active_tetrad: placeholder.clone(),
+            tetrad_shadow: placeholder,
+            queue,
+            garbage: GarbageMeter::new(settings.seed),
+            held_tetrad: None,
+            can_hold: true,
+            score: 0,
+            lines: 0,
+            level: 1,
+            game_over: false,
+            finish: None,
+            last_clear: None,
+            back_to_back: false,
+            combo: 0,
+            settings,
+            frames: 0,
+            pieces: 0,
+            splits: Vec::new(),
+            outgoing: 0,
+            lock_timer: None,
+            lock_resets: 0,
+            touched_down: false,
+            lowest_row: 0,
+            gravity_timer: Duration::from_millis(0),
+            last_kick: None,
+            spawn_timer: None,
+            initial_hold: false,
+            initial_rotation: None,
+        };
+        let tetrad = game.queue.next_tetrad();
+        game.enter(tetrad, None);
+        game
+    }
+
+    pub fn apply(&mut self, action: Action) {
+        if self.game_over {
+            return
+        }
+        if self.spawning() {
+            match action {
+                Action::Hold => self.initial_hold = true,
+                Action::RotateLeft => self.initial_rotation = Some(false),
+                Action::RotateRight => self.initial_rotation = Some(true),
+                _ => (),
+            }
+            return
+        }
+        let was_moved = match action {
+            Action::MoveLeft => self.move_left(),
+            Action::MoveRight => self.move_right(),
+            Action::SoftDrop => {
+                self.move_down(self.level);
+                false
+            },
+            Action::HardDrop => {
+                self.hard_drop();
+                self.lock_tetrad();
+                false
+            },
+            Action::RotateLeft => self.rotate(false),
+            Action::RotateRight => self.rotate(true),
+            Action::Hold => {
+                self.hold();
+                false
+            },
+        };
+        if was_moved {
+            self.reset_lock_timer();
+        }
+    }
+
+    pub fn drop_interval(&self) -> Duration {
+        self.settings.gravity.drop_interval(self.level)
+    }
+
+    // the whole simulation: the inputs of one frame go in, the game advances one frame
+    pub fn step(&mut self, inputs: &[Action]) {
+        for action in inputs {
+            self.apply(*action);
+        }
+        self.tick();
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.snapshot_with(Ghost::Outline)
+    }
+
+    // a snapshot with the shadow drawn in the given ghost style
+    pub fn snapshot_with(&self, ghost: Ghost) -> Snapshot {
+        let mut shadow = self.tetrad_shadow.clone();
+        let styled: Option<Vec<Tile>> = shadow.tiles.iter().map(|tile| ghost.style(*tile)).collect();
+        let board = match styled {
+            // the last tetrad is already part of the board
+            _ if self.spawning() => self.board.visible(),
+            Some(styled) => {
+                shadow.tiles.copy_from_slice(&styled);
+                self.board.visible_with(&[&shadow, &self.active_tetrad])
+            },
+            None => self.board.visible_with(&[&self.active_tetrad]),
+        };
+        Snapshot {
+            board,
+            next: self.previewed().cloned().collect(),
+            held: self.held_tetrad.as_ref().and_then(|name| self.settings.pieces.piece(name)),
+            preview_size: self.settings.pieces.preview_size(),
+            score: self.score,
+            lines: self.lines,
+            level: self.level,
+            last_clear: self.last_clear.clone(),
+            frames: self.frames,
+            game_over: self.game_over,
+            mode: self.settings.mode,
+            timer: self.settings.mode.timer(self.elapsed()),
+            splits: self.splits.clone(),
+            finish: self.finish,
+            garbage: self.garbage.total(),
+        }
+    }
+
+    // the upcoming tetrads the settings let anyone see, the next one first
+    pub fn previewed(&self) -> impl Iterator<Item = &Piece> {
+        self.queue.tetrads.iter().rev().take(self.settings.previews)
+    }
+
+    // game time, counted in frames so replays time out on the same frame
+    pub fn elapsed(&self) -> Duration {
+        frame_time(self.frames)
+    }
+
+    fn tick(&mut self) {
+        if self.game_over {
+            return
+        }
+        self.update(FRAME);
+        self.frames += 1;
+        match self.settings.mode.time_limit() {
+            Some(limit) if self.elapsed() >= limit => self.end(Finish::TimeUp),
+            _ => (),
+        }
+    }
+
+    pub fn receive_garbage(&mut self, lines: usize) {
+        if !self.game_over {
+            self.garbage.receive(lines);
+        }
+    }
+
+    // attack lines sent since the last call
+    pub fn take_attack(&mut self) -> usize {
+        std::mem::replace(&mut self.outgoing, 0)
+    }
+
+    fn end(&mut self, finish: Finish) {
+        if !self.game_over {
+            self.game_over = true;
+            self.finish = Some(finish);
+        }
+    }
+
+    // true between a lock and the next spawn, when there is no tetrad in play
+    pub fn spawning(&self) -> bool {
+        self.spawn_timer.is_some()
+    }
+
+    // advances gravity, the lock delay and the spawn delay by the time elapsed since the last update
+    fn update(&mut self, elapsed: Duration) {
+        if self.game_over {
+            return
+        }
+        if let Some(spawn_timer) = self.spawn_timer {
+            let spawn_timer = spawn_timer + elapsed;
+            if spawn_timer >= self.settings.spawn_delay {
+                self.spawn_next();
+            } else {
+                self.spawn_timer = Some(spawn_timer);
+            }
+            return
+        }
+        self.gravity_timer += elapsed;
+        let drop_interval = self.drop_interval();
+        while self.gravity_timer >= drop_interval {
+            self.gravity_timer -= drop_interval;
+            if !self.move_down(0) {
+                self.gravity_timer = Duration::from_millis(0);
+                break
+            }
+        }
+
+        if self.on_stack() {
+            self.touched_down = true;
+            // out of resets, a tetrad that lands again locks right away
+            let out_of_resets = self.lock_timer.is_none() && self.lock_resets >= MAX_LOCK_RESETS;
+            let lock_timer = self.lock_timer.unwrap_or_default() + elapsed;
+            if out_of_resets || lock_timer >= self.settings.lock_delay {
+                self.lock_tetrad();
+            } else {
+                self.lock_timer = Some(lock_timer);
+            }
+        } else {
+            self.lock_timer = None;
+        }
+    }
+
+    pub fn on_stack(&self) -> bool {
+        self.active_tetrad.get_position() == self.tetrad_shadow.get_position()
+    }
+
+    // moving or rotating on the stack restarts the lock delay, a limited number of times.
+    // after the first touchdown a move counts even when it lifts the tetrad off the stack
+    fn reset_lock_timer(&mut self) {
+        if self.touched_down && self.lock_resets < MAX_LOCK_RESETS {
+            self.lock_resets += 1;
+            if self.lock_timer.is_some() {
+                self.lock_timer = Some(Duration::from_millis(0));
+            }
+        }
+    }
+
+    fn bottom_row(&self) -> isize {
+        self.active_tetrad.tiles.iter().map(|tile| tile.row).max().unwrap_or(0)
+    }
+
+    fn spawn(&mut self, tetrad: Piece) -> bool {
+        let (tetrad, valid_spawn) = spawn_position(&self.board, tetrad);
+        self.active_tetrad = tetrad;
+        self.lock_timer = None;
+        self.lock_resets = 0;
+        self.touched_down = false;
+        self.lowest_row = self.bottom_row();
+        self.gravity_timer = Duration::from_millis(0);
+        self.last_kick = None;
+        valid_spawn
+    }
+
+    // spawns the tetrad turned by the initial rotation, or as it is if the turned one is blocked
+    fn spawn_rotated(&mut self, tetrad: Piece, rotation: Option<bool>) -> bool {
+        if let Some(clockwise) = rotation {
+            let rotated = tetrad.rotate(clockwise, (0, 0));
+            if spawn_position(&self.board, rotated.clone()).1 {
+                return self.spawn(rotated)
+            }
+        }
+        self.spawn(tetrad)
+    }
+
+    // brings in the next tetrad from the queue, or straight from hold with an initial hold
+    fn spawn_next(&mut self) {
+        self.spawn_timer = None;
+        let mut tetrad = self.queue.next_tetrad();
+        if std::mem::replace(&mut self.initial_hold, false) {
+            tetrad = self.swap_hold(tetrad.name);
+        }
+        let rotation = self.initial_rotation.take();
+        self.enter(tetrad, rotation);
+    }
+
+    // puts the tetrad in play, a blocked spawn is a block out
+    fn enter(&mut self, tetrad: Piece, rotation: Option<bool>) {
+        let mut valid_spawn = self.spawn_rotated(tetrad.clone(), rotation);
+        if !valid_spawn && !self.settings.mode.tops_out() {
+            // zen: start over on an empty board with the same tetrad
+            self.board = Board::new(self.settings.width, self.settings.height);
+            valid_spawn = self.spawn_rotated(tetrad, rotation);
+        }
+        self.update_shadow();
+        if !valid_spawn {
+            self.end(Finish::ToppedOut(TopOut::BlockOut));
+        }
+    }
+
+    fn update_level(&mut self) {
+        if self.settings.mode.levels_up() {
+            self.level = (self.lines / 10) + 1;
+        }
+    }
+
+    fn move_active_tetrad(&mut self, tetrad_mover: Box<dyn Fn(&mut Piece)>) -> bool {
+        let mut tetrad = self.active_tetrad.clone();
+        let was_moved = tetrad.move_tetrad(&self.board, tetrad_mover);
+        self.active_tetrad = tetrad;
+        self.update_shadow();
+        if was_moved {
+            self.last_kick = None;
+        }
+        was_moved
+    }
+
+    fn get_shadow(&self) -> Piece {
+        let mut shadow = self.active_tetrad.clone();
+        shadow.shift(self.board.drop_distance(&shadow), 0);
+        for tile in shadow.tiles.iter_mut() {
+            tile.utf8 = SQUARE_OUTLINE;
+        }
+        shadow
+    }
+
+    fn update_shadow(&mut self) {
+        self.tetrad_shadow = self.get_shadow();
+    }
+
+    fn hard_drop(&mut self) {
+        let color = self.active_tetrad.tiles[0].color;
+        let utf8 = self.active_tetrad.tiles[0].utf8;
+        let from_row = &self.active_tetrad.tiles[0].row;
+        let to_row = &self.tetrad_shadow.tiles[0].row;
+        let rows_dropped = to_row - from_row;
+        self.score += rows_dropped as usize * self.level * 2;
+        if rows_dropped > 0 {
+            self.last_kick = None;
+        }
+        self.active_tetrad.tiles = self.tetrad_shadow.tiles.clone();
+        for tile in self.active_tetrad.tiles.iter_mut() {
+            tile.color = color;
+            tile.utf8 = utf8;
+        }
+    }
+
+    //TODO
+    //better python like function wrapping?
+    fn move_left(&mut self) -> bool {
+        fn move_tetrad_left(tetrad: &mut Piece) {
+            tetrad.shift(0, -1);
+        }
+        self.move_active_tetrad(Box::new(move_tetrad_left))
+    }
+
+    fn move_right(&mut self) -> bool {
+        fn move_tetrad_right(tetrad: &mut Piece) {
+            tetrad.shift(0, 1);
+        }
+        self.move_active_tetrad(Box::new(move_tetrad_right))
+    }
+
+    fn move_down(&mut self, score: usize) -> bool {
+        fn move_tetrad_down(tetrad: &mut Piece) {
+            tetrad.shift(1, 0);
+        }
+        let was_moved = self.move_active_tetrad(Box::new(move_tetrad_down));
+        if was_moved {
+            self.score += score;
+            // reaching a new lowest row gives back the lock delay resets
+            let bottom_row = self.bottom_row();
+            if bottom_row > self.lowest_row {
+                self.lowest_row = bottom_row;
+                self.lock_resets = 0;
+            }
+        }
+        was_moved
+    }
+
+    fn rotate(&mut self, clockwise: bool) -> bool {
+        let board = &self.board;
+        let kicked = self.active_tetrad.kicks(clockwise)
+            .iter()
+            .map(|kick| self.active_tetrad.rotate(clockwise, *kick))
+            .enumerate()
+            .find(|(_, tetrad)| board.valid_tetrad(tetrad));
+        let was_moved = kicked.is_some();
+        if let Some((kick, tetrad)) = kicked {
+            self.active_tetrad = tetrad;
+            self.last_kick = Some(kick);
+        }
+        self.update_shadow();
+        was_moved
+    }
+
+    // 3-corner rule: a T that rotated into place with three of the corners
+    // around its center filled. only one filled corner on the side it points
+    // to makes it a mini, unless it got there with the last kick
+    fn detect_spin(&self) -> Spin {
+        let kick = match self.last_kick {
+            Some(kick) if self.active_tetrad.name == "T" => kick,
+            _ => return Spin::None,
+        };
+        let (row, column) = self.active_tetrad.origin;
+        let filled = |corner: (isize, isize)| {
+            self.board.occupied(row + corner.0, column + corner.1)
+        };
+        let corners = [(0, 0), (0, 2), (2, 2), (2, 0)];
+        if corners.iter().filter(|corner| filled(**corner)).count() < 3 {
+            return Spin::None
+        }
+        let front = match self.active_tetrad.rotation {
+            Rotation::Zero => [(0, 0), (0, 2)],
+            Rotation::Right => [(0, 2), (2, 2)],
+            Rotation::Two => [(2, 2), (2, 0)],
+            Rotation::Left => [(2, 0), (0, 0)],
+        };
+        if front.iter().all(|corner| filled(*corner)) || kick == 4 {
+            Spin::Full
+        } else {
+            Spin::Mini
+        }
+    }
+
+    // a clear spends its attack on waiting garbage before sending the rest,
+    // a tetrad locked without a clear lets all waiting garbage rise
+    fn rise_garbage(&mut self, lines: usize) -> bool {
+        if lines > 0 {
+            let attack = self.last_clear.as_ref().map(Clear::attack).unwrap_or(0);
+            self.outgoing += self.garbage.offset(attack);
+            return true
+        }
+        let mut valid = true;
+        for (rows, hole) in self.garbage.rise(self.settings.width) {
+            valid &= self.board.add_garbage(rows, hole, garbage::garbage_tile());
+        }
+        valid
+    }
+
+    fn score_clear(&mut self, lines: usize, spin: Spin) {
+        if lines == 0 {
+            self.combo = 0;
+        }
+        if lines == 0 && spin == Spin::None {
+            return
+        }
+        let mut clear = Clear { lines, spin, back_to_back: false, combo: self.combo };
+        if lines > 0 {
+            clear.back_to_back = clear.difficult() && self.back_to_back;
+            self.back_to_back = clear.difficult();
+            self.combo += 1;
+        }
+        self.score += clear.score(self.level);
+        self.last_clear = Some(clear);
+    }
+
+    // once per tetrad, the swapped in tetrad spawns like a new one and a blocked spawn ends the game
+    fn hold(&mut self) {
+        if !self.can_hold {
+            return
+        }
+        let tetrad = self.swap_hold(self.active_tetrad.name.clone());
+        self.enter(tetrad, None);
+    }
+
+    // puts the named tetrad in hold and takes out what was there, or the next one if hold was empty
+    fn swap_hold(&mut self, name: String) -> Piece {
+        self.can_hold = false;
+        match self.held_tetrad.replace(name) {
+            Some(held) => self.settings.pieces.piece(&held).expect("held tetrads come from the piece set"),
+            None => self.queue.next_tetrad(),
+        }
+    }
+
+    fn lock_tetrad(&mut self) {
+        self.pieces += 1;
+
+        let spin = self.detect_spin();
+        self.board.lock(&self.active_tetrad);
+        // zen plays on, a blocked spawn clears its board
+        let above_field = self.active_tetrad.tiles.iter().all(|tile| tile.row < 0);
+        if above_field && self.settings.mode.tops_out() {
+            self.end(Finish::ToppedOut(TopOut::LockOut));
+            return
+        }
+
+        let full_rows = self.board.full_rows();
+        let n_full_rows = full_rows.len();
+        self.score_clear(n_full_rows, spin);
+        if n_full_rows > 0 {
+            self.board.clear_rows(full_rows);
+            for _ in 0..(self.lines + n_full_rows) / SPLIT_LINES - self.lines / SPLIT_LINES {
+                self.splits.push(self.frames);
+            }
+            self.lines += n_full_rows;
+            self.update_level();
+        }
+        match self.settings.mode.line_goal() {
+            Some(goal) if self.lines >= goal => {
+                self.end(Finish::Completed);
+                return
+            },
+            _ => (),
+        }
+        if !self.rise_garbage(n_full_rows) {
+            self.end(Finish::ToppedOut(TopOut::Garbage));
+            return
+        }
+
+        self.can_hold = true;
+        if self.settings.spawn_delay > Duration::from_millis(0) {
+            self.spawn_timer = Some(Duration::from_millis(0));
+        } else {
+            self.spawn_next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotating_on_the_floor_runs_out_of_lock_resets() {
+        let mut game = (0..)
+            .map(|seed| Game::new(Settings { seed, ..Default::default() }))
+            .find(|game| game.active_tetrad.name == "S")
+            .unwrap();
+        while !game.on_stack() {
+            game.step(&[Action::SoftDrop]);
+        }
+        for frame in 0..600 {
+            if game.pieces > 0 {
+                return
+            }
+            let action = if frame % 2 == 0 { Action::RotateRight } else { Action::RotateLeft };
+            game.step(&[action]);
+        }
+        panic!("the tetrad never locked");
+    }
+
+    #[test]
+    fn the_same_inputs_play_the_same_game() {
+        let settings = Settings { seed: 42, ..Default::default() };
+        let mut games = [Game::new(settings.clone()), Game::new(settings)];
+        let moves = [Action::RotateLeft, Action::MoveRight, Action::SoftDrop, Action::MoveLeft, Action::HardDrop];
+        for frame in 0..2000 {
+            let inputs = match frame % 5 {
+                0 => vec![moves[frame / 5 % moves.len()]],
+                _ => Vec::new(),
+            };
+            for game in games.iter_mut() {
+                game.step(&inputs);
+            }
+            if frame % 100 == 0 {
+                assert_eq!(format!("{:?}", games[0].snapshot()), format!("{:?}", games[1].snapshot()));
+            }
+        }
+        assert_eq!(format!("{:?}", games[0].snapshot()), format!("{:?}", games[1].snapshot()));
+        assert!(games[0].pieces > 0);
+    }
+}
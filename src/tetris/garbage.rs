@@ -0,0 +1,83 @@
+extern crate rand;
+
+use std::collections::VecDeque;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::grid::grid::Grid;
+use crate::grid::rgb::RGB;
+
+use super::tile::{Tile, SQUARE, OUTLINED_SQUARE};
+
+pub const GARBAGE_COLOR: RGB = RGB { r: 128, g: 128, b: 128 };
+pub const METER_COLOR: RGB = RGB { r: 255, g: 51, b: 51 };
+
+pub fn garbage_tile() -> Tile {
+    Tile { empty: false, color: GARBAGE_COLOR, utf8: SQUARE, ..Default::default() }
+}
+
+// garbage sent by the opponent, waiting to rise until a tetrad locks without clearing lines
+pub struct GarbageMeter {
+    // one entry per attack, every row of an attack shares its hole
+    pub incoming: VecDeque<usize>,
+    rng: StdRng,
+}
+
+impl GarbageMeter {
+
+    pub fn new(seed: u64) -> GarbageMeter {
+        GarbageMeter { incoming: VecDeque::new(), rng: StdRng::seed_from_u64(seed) }
+    }
+
+    pub fn receive(&mut self, lines: usize) {
+        if lines > 0 {
+            self.incoming.push_back(lines);
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.incoming.iter().sum()
+    }
+
+    // an attack cancels waiting garbage first, whatever is left over gets sent
+    pub fn offset(&mut self, mut attack: usize) -> usize {
+        while attack > 0 {
+            match self.incoming.front_mut() {
+                Some(lines) if *lines > attack => {
+                    *lines -= attack;
+                    attack = 0;
+                },
+                Some(lines) => {
+                    attack -= *lines;
+                    self.incoming.pop_front();
+                },
+                None => break,
+            }
+        }
+        attack
+    }
+
+    // empties the meter into (rows, hole column) pairs
+    pub fn rise(&mut self, width: usize) -> Vec<(usize, usize)> {
+        let mut rising = Vec::new();
+        while let Some(lines) = self.incoming.pop_front() {
+            rising.push((lines, self.rng.gen_range(0, width)));
+        }
+        rising
+    }
+}
+
+// a one column bar filled from the bottom, drawn next to a board
+pub fn meter(lines: usize, height: usize) -> Grid<Tile> {
+    let mut tiles = Vec::new();
+    for row in 0..height {
+        let mut tile = Tile::new(row as isize, 0);
+        if height - row <= lines {
+            tile.color = METER_COLOR;
+            tile.utf8 = OUTLINED_SQUARE;
+        }
+        tiles.push(tile);
+    }
+    Grid::new(1, height, tiles)
+}
@@ -0,0 +1,94 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const FRAMES_PER_SECOND: f64 = 60.;
+// a tetrad at 20G reaches the floor on the frame it spawns
+pub const MAX_GRAVITY: f64 = 20.;
+
+// cells per frame by level, each entry holds until the next one
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gravity {
+    pub levels: Vec<(usize, f64)>,
+}
+
+impl Gravity {
+
+    // seconds per row = (0.8 - (level - 1) * 0.007) ^ (level - 1), 20G from level 20
+    pub fn guideline() -> Gravity {
+        let mut levels = Vec::new();
+        for level in 1..20 {
+            let exponent = level as i32 - 1;
+            let seconds_per_row = (0.8 - exponent as f64 * 0.007).powi(exponent);
+            let cells_per_frame = 1. / (seconds_per_row * FRAMES_PER_SECOND);
+            levels.push((level, cells_per_frame.min(MAX_GRAVITY)));
+        }
+        levels.push((20, MAX_GRAVITY));
+        Gravity { levels }
+    }
+
+    pub fn cells_per_frame(&self, level: usize) -> f64 {
+        self.levels
+            .iter()
+            .take_while(|(from_level, _)| *from_level <= level)
+            .last()
+            .or_else(|| self.levels.first())
+            .map(|(_, cells_per_frame)| *cells_per_frame)
+            .unwrap_or(0.)
+    }
+
+    // at least one level, levels in order and never a negative or missing speed
+    pub fn check(&self) -> Result<(), String> {
+        if self.levels.is_empty() {
+            return Err("gravity curve has no levels".to_string())
+        }
+        if self.levels.windows(2).any(|pair| pair[0].0 > pair[1].0) {
+            return Err("gravity levels must be in order".to_string())
+        }
+        match self.levels.iter().find(|(_, cells_per_frame)| cells_per_frame.is_nan() || *cells_per_frame < 0.) {
+            Some((level, _)) => Err(format!("gravity at level {} must be at least 0 cells per frame", level)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn drop_interval(&self, level: usize) -> Duration {
+        let cells_per_frame = self.cells_per_frame(level).min(MAX_GRAVITY);
+        // no gravity, or so little that the interval overflows, never drops
+        Duration::try_from_secs_f64(1. / (cells_per_frame * FRAMES_PER_SECOND))
+            .unwrap_or_else(|_| Duration::from_secs(u64::MAX))
+    }
+}
+
+impl Default for Gravity {
+    fn default() -> Gravity {
+        Gravity::guideline()
+    }
+}
+
+// one `level cells_per_frame` pair per line, # starts a comment
+impl FromStr for Gravity {
+    type Err = String;
+
+    fn from_str(data: &str) -> Result<Gravity, String> {
+        let mut levels = Vec::new();
+        for (number, line) in data.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match fields.as_slice() {
+                [level, cells_per_frame] => level.parse::<usize>().ok()
+                    .zip(cells_per_frame.parse::<f64>().ok()),
+                _ => None,
+            };
+            match parsed {
+                Some(entry) => levels.push(entry),
+                None => return Err(format!("gravity line {}: expected `level cells_per_frame`", number + 1)),
+            }
+        }
+        levels.sort_by_key(|(level, _)| *level);
+        let gravity = Gravity { levels };
+        gravity.check()?;
+        Ok(gravity)
+    }
+}
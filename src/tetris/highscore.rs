@@ -0,0 +1,145 @@
+extern crate serde;
+extern crate serde_json;
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::game::Game;
+use super::mode::{Finish, Mode};
+use super::dirs;
+
+// entries kept for each mode
+pub const TABLE_SIZE: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub score: usize,
+    pub lines: usize,
+    pub level: usize,
+    // game time
+    pub frames: u64,
+    // seconds since the unix epoch
+    pub date: u64,
+}
+
+impl Entry {
+
+    pub fn new(game: &Game, name: &str) -> Entry {
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        Entry {
+            name: name.chars().take(MAX_NAME_LENGTH).collect(),
+            score: game.score,
+            lines: game.lines,
+            level: game.level,
+            frames: game.frames,
+            date,
+        }
+    }
+
+    // whether this entry goes above the other one, ties keep the older entry on top
+    fn beats(&self, other: &Entry, mode: Mode) -> bool {
+        if mode.ranked_by_time() {
+            self.frames < other.frames
+        } else {
+            self.score > other.score
+        }
+    }
+}
+
+// the best games of one mode, best first
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighScores {
+    pub mode: Mode,
+    pub entries: Vec<Entry>,
+    // where the table is loaded from and saved to, None keeps it in memory
+    pub path: Option<PathBuf>,
+}
+
+// the per user data directory: $XDG_DATA_HOME or ~/.local/share,
+// ~/Library/Application Support on macos and %APPDATA% on windows
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::user_dir("XDG_DATA_HOME", &[".local", "share"])
+}
+
+impl HighScores {
+
+    pub fn new(mode: Mode) -> HighScores {
+        HighScores { mode, entries: Vec::new(), path: None }
+    }
+
+    // the table in the data directory, empty if it was never saved
+    pub fn load(mode: Mode) -> io::Result<HighScores> {
+        let path = data_dir()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no data directory"))?
+            .join(format!("highscores-{}.json", mode.name()));
+        let entries = match fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data)
+                .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?,
+            Err(error) if error.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error),
+        };
+        Ok(HighScores { mode, entries, path: Some(path) })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let data = serde_json::to_string_pretty(&self.entries)
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+        fs::write(path, data)
+    }
+
+    // where a finished game would land in the table, None if it doesn't make it.
+    // sprint only counts finished runs and the other modes need some points
+    pub fn rank(&self, game: &Game) -> Option<usize> {
+        let counts = match game.finish {
+            Some(Finish::Completed) => true,
+            Some(_) => !self.mode.ranked_by_time() && game.score > 0,
+            None => false,
+        };
+        if !counts || !self.mode.keeps_high_scores() {
+            return None
+        }
+        let entry = Entry::new(game, "");
+        let rank = self.entries.iter().take_while(|other| !entry.beats(other, self.mode)).count();
+        Some(rank).filter(|rank| *rank < TABLE_SIZE)
+    }
+
+    // adds the entry in its place and returns that place, None if it fell off the bottom
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        let mode = self.mode;
+        let rank = self.entries.iter().take_while(|other| !entry.beats(other, mode)).count();
+        self.entries.insert(rank, entry);
+        self.entries.truncate(TABLE_SIZE);
+        Some(rank).filter(|rank| *rank < TABLE_SIZE)
+    }
+}
+
+// year-month-day in utc
+pub fn format_date(seconds: u64) -> String {
+    // days to a civil date, from Howard Hinnant's date algorithms
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{}-{:02}-{:02}", year, month, day)
+}
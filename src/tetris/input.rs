@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const DEFAULT_DAS: Duration = Duration::from_millis(167);
+pub const DEFAULT_ARR: Duration = Duration::from_millis(33);
+
+// terminals only report presses, so a key counts as held while the terminal
+// keeps repeating it. the first repeat only shows up after the terminal's own
+// repeat delay, later ones arrive every few dozen milliseconds
+pub const DEFAULT_PRESS_TIMEOUT: Duration = Duration::from_millis(700);
+pub const DEFAULT_REPEAT_TIMEOUT: Duration = Duration::from_millis(60);
+// presses closer together than this are taps, not terminal repeats
+pub const MIN_REPEAT_DELAY: Duration = Duration::from_millis(150);
+
+// with an ARR of zero a held key moves as far as it can every update
+const INSTANT_REPEATS: usize = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Escape,
+    Backspace,
+    Tab,
+}
+
+impl Key {
+
+    // how the key is shown on the help screen
+    pub fn label(self) -> String {
+        match self {
+            Key::Char(' ') => "SPACE".to_string(),
+            Key::Char(character) => character.to_uppercase().to_string(),
+            Key::Up => "⇧".to_string(),
+            Key::Down => "⇩".to_string(),
+            Key::Left => "⇦".to_string(),
+            Key::Right => "⇨".to_string(),
+            Key::Enter => "ENTER".to_string(),
+            Key::Escape => "ESC".to_string(),
+            Key::Backspace => "BACKSPACE".to_string(),
+            Key::Tab => "TAB".to_string(),
+        }
+    }
+}
+
+// names as written in the settings file, letters are case insensitive
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Key, String> {
+        let lowercase = name.to_lowercase();
+        let mut characters = lowercase.chars();
+        match (characters.next(), characters.next()) {
+            (Some(character), None) if (' '..='~').contains(&character) => return Ok(Key::Char(character)),
+            _ => (),
+        }
+        match lowercase.as_str() {
+            "space" => Ok(Key::Char(' ')),
+            "up" => Ok(Key::Up),
+            "down" => Ok(Key::Down),
+            "left" => Ok(Key::Left),
+            "right" => Ok(Key::Right),
+            "enter" => Ok(Key::Enter),
+            "escape" | "esc" => Ok(Key::Escape),
+            "backspace" => Ok(Key::Backspace),
+            "tab" => Ok(Key::Tab),
+            _ => Err(format!("unknown key: {}", name)),
+        }
+    }
+}
+
+// turns raw terminal bytes into keys, keeping incomplete escape sequences for the next call
+#[derive(Default)]
+pub struct Decoder {
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+
+    pub fn new() -> Decoder {
+        Decoder { pending: Vec::new() }
+    }
+
+    // a lone escape is only reported once a call brings no new bytes
+    pub fn decode(&mut self, bytes: &[u8]) -> Vec<Key> {
+        self.pending.extend_from_slice(bytes);
+        let mut keys = Vec::new();
+        let mut index = 0;
+        while index < self.pending.len() {
+            match self.pending[index] {
+                27 => match self.pending.get(index + 1) {
+                    None if bytes.is_empty() => {
+                        keys.push(Key::Escape);
+                        index += 1;
+                    },
+                    None => break,
+                    Some(b'[') | Some(b'O') => {
+                        // parameters up to the final byte of the sequence
+                        let end = self.pending[index + 2..]
+                            .iter()
+                            .position(|byte| (0x40..=0x7E).contains(byte));
+                        match end {
+                            Some(end) => {
+                                let final_index = index + 2 + end;
+                                match self.pending[final_index] {
+                                    b'A' => keys.push(Key::Up),
+                                    b'B' => keys.push(Key::Down),
+                                    b'C' => keys.push(Key::Right),
+                                    b'D' => keys.push(Key::Left),
+                                    _ => (),
+                                }
+                                index = final_index + 1;
+                            },
+                            None if bytes.is_empty() => {
+                                keys.push(Key::Escape);
+                                index = self.pending.len();
+                            },
+                            None => break,
+                        }
+                    },
+                    Some(_) => {
+                        keys.push(Key::Escape);
+                        index += 1;
+                    },
+                },
+                b'\r' | b'\n' => {
+                    keys.push(Key::Enter);
+                    index += 1;
+                },
+                127 | 8 => {
+                    keys.push(Key::Backspace);
+                    index += 1;
+                },
+                b'\t' => {
+                    keys.push(Key::Tab);
+                    index += 1;
+                },
+                byte => {
+                    if (0x20..0x7F).contains(&byte) {
+                        keys.push(Key::Char(byte as char));
+                    }
+                    index += 1;
+                },
+            }
+        }
+        self.pending.drain(..index);
+        keys
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct HeldKey {
+    held_for: Duration,
+    since_event: Duration,
+    // pressed again after a pause, which is either the terminal's first repeat or another tap
+    late: bool,
+    repeating: bool,
+    charged: bool,
+    repeat_timer: Duration,
+}
+
+impl HeldKey {
+
+    fn new() -> HeldKey {
+        HeldKey {
+            held_for: Duration::from_millis(0),
+            since_event: Duration::from_millis(0),
+            late: false,
+            repeating: false,
+            charged: false,
+            repeat_timer: Duration::from_millis(0),
+        }
+    }
+}
+
+// tracks held keys and applies Delayed Auto Shift and Auto Repeat Rate
+// on our own clock instead of the terminal's repeat rate
+pub struct Keyboard {
+    pub das: Duration,
+    pub arr: Duration,
+    pub press_timeout: Duration,
+    pub repeat_timeout: Duration,
+    keys: HashMap<Key, HeldKey>,
+}
+
+impl Keyboard {
+
+    pub fn new(das: Duration, arr: Duration) -> Keyboard {
+        Keyboard {
+            das,
+            arr,
+            press_timeout: DEFAULT_PRESS_TIMEOUT,
+            repeat_timeout: DEFAULT_REPEAT_TIMEOUT,
+            keys: HashMap::new(),
+        }
+    }
+
+    // returns true for a new press, false for a terminal repeat of a held key.
+    // a late press counts as a new one, only another event right after it shows the key is held
+    pub fn press(&mut self, key: Key) -> bool {
+        let repeat_timeout = self.repeat_timeout;
+        match self.keys.get_mut(&key) {
+            Some(held) if held.repeating || (held.late && held.since_event <= repeat_timeout) => {
+                held.repeating = true;
+                held.since_event = Duration::from_millis(0);
+                false
+            },
+            Some(held) if held.held_for >= MIN_REPEAT_DELAY => {
+                self.keys.insert(key, HeldKey { late: true, ..HeldKey::new() });
+                true
+            },
+            _ => {
+                self.keys.insert(key, HeldKey::new());
+                true
+            },
+        }
+    }
+
+    pub fn is_held(&self, key: Key) -> bool {
+        self.keys.contains_key(&key)
+    }
+
+    pub fn held(&self) -> Vec<Key> {
+        self.keys.keys().cloned().collect()
+    }
+
+    // keys the terminal is repeating, known to be held down rather than tapped
+    pub fn repeating(&self) -> Vec<Key> {
+        self.keys.iter()
+            .filter(|(_, held)| held.repeating)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    pub fn release_all(&mut self) {
+        self.keys.clear();
+    }
+
+    // advances the clock, returning the auto repeats of held keys in the order they fired
+    pub fn update(&mut self, elapsed: Duration) -> Vec<Key> {
+        let press_timeout = self.press_timeout;
+        let repeat_timeout = self.repeat_timeout;
+        self.keys.retain(|_, held| {
+            held.since_event += elapsed;
+            let timeout = if held.repeating { repeat_timeout } else { press_timeout };
+            held.since_event <= timeout
+        });
+
+        let mut repeats = Vec::new();
+        for (key, held) in self.keys.iter_mut() {
+            held.held_for += elapsed;
+            if !held.repeating || held.held_for < self.das {
+                continue
+            }
+            if !held.charged {
+                // the first repeat fires as soon as the auto shift is charged
+                held.charged = true;
+                repeats.push(*key);
+                held.repeat_timer = Duration::from_millis(0);
+            } else {
+                held.repeat_timer += elapsed;
+            }
+            if self.arr == Duration::from_millis(0) {
+                for _ in 0..INSTANT_REPEATS {
+                    repeats.push(*key);
+                }
+                held.repeat_timer = Duration::from_millis(0);
+            } else {
+                while held.repeat_timer >= self.arr {
+                    held.repeat_timer -= self.arr;
+                    repeats.push(*key);
+                }
+            }
+        }
+        repeats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_nanos(16_666_667);
+
+    // every move a key makes: presses that count as new plus the auto repeats
+    fn moves(keyboard: &mut Keyboard, taps: &[Duration]) -> usize {
+        let mut moves = 0;
+        let mut elapsed = Duration::from_millis(0);
+        for tap in taps {
+            while elapsed < *tap {
+                moves += keyboard.update(FRAME).len();
+                elapsed += FRAME;
+            }
+            if keyboard.press(Key::Left) {
+                moves += 1;
+            }
+        }
+        for _ in 0..120 {
+            moves += keyboard.update(FRAME).len();
+        }
+        moves
+    }
+
+    #[test]
+    fn two_slow_taps_are_two_moves() {
+        let taps = [Duration::from_millis(0), Duration::from_millis(200)];
+        assert_eq!(moves(&mut Keyboard::new(DEFAULT_DAS, DEFAULT_ARR), &taps), 2);
+        assert_eq!(moves(&mut Keyboard::new(DEFAULT_DAS, Duration::from_millis(0)), &taps), 2);
+    }
+
+    #[test]
+    fn a_held_key_auto_repeats() {
+        // the terminal's first repeat after half a second, then one every 30ms
+        let mut taps = vec![Duration::from_millis(0)];
+        taps.extend((500..1000).step_by(30).map(Duration::from_millis));
+        assert!(moves(&mut Keyboard::new(DEFAULT_DAS, DEFAULT_ARR), &taps) > 10);
+    }
+}
@@ -1,2 +1,23 @@
 pub mod tile;
 pub mod tetrad;
+pub mod randomizer;
+pub mod srs;
+pub mod board;
+pub mod input;
+pub mod scoring;
+pub mod gravity;
+pub mod clock;
+pub mod mode;
+pub mod garbage;
+pub mod game;
+pub mod replay;
+pub mod versus;
+pub mod net;
+pub mod ai;
+pub mod tbp;
+pub mod env;
+pub mod highscore;
+pub mod scene;
+pub mod config;
+pub mod piece;
+pub mod dirs;
@@ -0,0 +1,147 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+// lines between two split times
+pub const SPLIT_LINES: usize = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Mode {
+    // levels go up every 10 lines until 150 lines are cleared
+    #[default]
+    Marathon,
+    // 40 lines as fast as possible
+    Sprint,
+    // as many points as possible in 2 minutes
+    Ultra,
+    // no timer, no goal and no top out
+    Zen,
+    // two players sending each other garbage until one tops out
+    Versus,
+    // levels go up every 10 lines with no goal, the game only ends at top out
+    Endless,
+}
+
+// the guideline ways to lose
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TopOut {
+    // a new tetrad overlaps the stack where it spawns
+    BlockOut,
+    // a tetrad locked entirely above the visible field
+    LockOut,
+    // garbage pushed the stack out of the top of the buffer
+    Garbage,
+}
+
+impl TopOut {
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TopOut::BlockOut => "block out",
+            TopOut::LockOut => "lock out",
+            TopOut::Garbage => "top out",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Finish {
+    // the line goal was reached
+    Completed,
+    TimeUp,
+    ToppedOut(TopOut),
+}
+
+impl Finish {
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Finish::Completed => "complete",
+            Finish::TimeUp => "time up",
+            Finish::ToppedOut(top_out) => top_out.name(),
+        }
+    }
+
+    pub fn topped_out(self) -> bool {
+        matches!(self, Finish::ToppedOut(_))
+    }
+}
+
+impl Mode {
+
+    pub fn line_goal(self) -> Option<usize> {
+        match self {
+            Mode::Marathon => Some(150),
+            Mode::Sprint => Some(40),
+            _ => None,
+        }
+    }
+
+    pub fn time_limit(self) -> Option<Duration> {
+        match self {
+            Mode::Ultra => Some(Duration::from_secs(120)),
+            _ => None,
+        }
+    }
+
+    // the other modes stay at level 1 gravity
+    pub fn levels_up(self) -> bool {
+        self == Mode::Marathon || self == Mode::Endless
+    }
+
+    // zen clears the board instead of ending the game
+    pub fn tops_out(self) -> bool {
+        self != Mode::Zen
+    }
+
+    // what the stats panel shows: time left when there is a limit, otherwise time played
+    pub fn timer(self, elapsed: Duration) -> Option<Duration> {
+        match (self, self.time_limit()) {
+            (Mode::Zen, _) => None,
+            (_, Some(limit)) => Some(limit.checked_sub(elapsed).unwrap_or_default()),
+            (_, None) => Some(elapsed),
+        }
+    }
+
+    // versus has no high score table
+    pub fn keeps_high_scores(self) -> bool {
+        self != Mode::Versus
+    }
+
+    // sprint high scores go to the fastest finish, the others to the most points
+    pub fn ranked_by_time(self) -> bool {
+        self == Mode::Sprint
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Marathon => "marathon",
+            Mode::Sprint => "sprint",
+            Mode::Ultra => "ultra",
+            Mode::Zen => "zen",
+            Mode::Versus => "versus",
+            Mode::Endless => "endless",
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Mode, String> {
+        match name {
+            "marathon" => Ok(Mode::Marathon),
+            "sprint" => Ok(Mode::Sprint),
+            "ultra" => Ok(Mode::Ultra),
+            "zen" => Ok(Mode::Zen),
+            "versus" => Ok(Mode::Versus),
+            "endless" => Ok(Mode::Endless),
+            _ => Err(format!("unknown mode: {}", name)),
+        }
+    }
+}
+
+// minutes:seconds.hundredths
+pub fn format_time(time: Duration) -> String {
+    let hundredths = time.as_millis() / 10;
+    format!("{}:{:02}.{:02}", hundredths / 6000, hundredths / 100 % 60, hundredths % 100)
+}
@@ -0,0 +1,587 @@
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::grid::grid::Grid;
+
+use super::tile::Tile;
+use super::tetrad;
+use super::piece::PieceSet;
+use super::randomizer::RandomizerKind;
+use super::gravity::Gravity;
+use super::game::{Action, Game, Settings, Snapshot};
+use super::mode::Mode;
+use super::garbage;
+use super::versus::PLAYERS;
+
+// one message per line, fields separated by spaces
+// bump the version whenever a message changes shape
+pub const PROTOCOL_VERSION: u32 = 3;
+pub const DEFAULT_PORT: u16 = 7070;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// the host is always player 0, whoever joins as a player is player 1
+pub const HOST: usize = 0;
+pub const GUEST: usize = 1;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Role {
+    Player,
+    // read only, receives every board update
+    Spectator,
+}
+
+impl Role {
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Player => "player",
+            Role::Spectator => "spectator",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Role, String> {
+        match name {
+            "player" => Ok(Role::Player),
+            "spectator" => Ok(Role::Spectator),
+            _ => Err(format!("unknown role: {}", name)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    // the first line from whoever connects
+    Hello { version: u32, role: Role },
+    // the host's answer, both players build their games from it
+    Welcome {
+        version: u32,
+        seed: u64,
+        randomizer: RandomizerKind,
+        width: usize,
+        height: usize,
+        previews: usize,
+        lock_delay: Duration,
+        spawn_delay: Duration,
+        gravity: Gravity,
+    },
+    // the host's answer to a hello it can't take, the connection closes after it
+    Refused { reason: String },
+    // a tetrad locked, with the player's board and counters after the lock
+    Lock { player: usize, score: usize, lines: usize, garbage: usize, rows: Vec<String> },
+    // attack lines for the receiver's garbage meter
+    Garbage { lines: usize },
+    GameOver { player: usize },
+}
+
+impl Message {
+
+    pub fn encode(&self) -> String {
+        match self {
+            Message::Hello { version, role } =>
+                format!("hello {} {}", version, role.name()),
+            Message::Welcome { version, seed, randomizer, width, height, previews, lock_delay, spawn_delay, gravity } =>
+                format!("welcome {} {} {} {} {} {} {} {} {}", version, seed, randomizer.name(), width, height, previews,
+                    lock_delay.as_millis(), spawn_delay.as_millis(), encode_gravity(gravity)),
+            Message::Refused { reason } =>
+                format!("refused {}", reason),
+            Message::Lock { player, score, lines, garbage, rows } =>
+                format!("lock {} {} {} {} {}", player, score, lines, garbage, rows.join(" ")),
+            Message::Garbage { lines } =>
+                format!("garbage {}", lines),
+            Message::GameOver { player } =>
+                format!("over {}", player),
+        }
+    }
+
+    pub fn decode(line: &str) -> Result<Message, String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let message = match fields.as_slice() {
+            ["hello", version, role] =>
+                Message::Hello { version: number(version)?, role: role.parse()? },
+            ["welcome", version, seed, randomizer, width, height, previews, lock_delay, spawn_delay, gravity] => Message::Welcome {
+                version: number(version)?,
+                seed: number(seed)?,
+                randomizer: randomizer.parse()?,
+                width: number(width)?,
+                height: number(height)?,
+                previews: number(previews)?,
+                lock_delay: Duration::from_millis(number(lock_delay)?),
+                spawn_delay: Duration::from_millis(number(spawn_delay)?),
+                gravity: decode_gravity(gravity)?,
+            },
+            ["refused", reason @ ..] =>
+                Message::Refused { reason: reason.join(" ") },
+            ["lock", player, score, lines, garbage, rows @ ..] => Message::Lock {
+                player: player_number(player)?,
+                score: number(score)?,
+                lines: number(lines)?,
+                garbage: number(garbage)?,
+                rows: rows.iter().map(|row| row.to_string()).collect(),
+            },
+            ["garbage", lines] =>
+                Message::Garbage { lines: number(lines)? },
+            ["over", player] =>
+                Message::GameOver { player: player_number(player)? },
+            _ => return Err(format!("bad message: {}", line)),
+        };
+        Ok(message)
+    }
+}
+
+fn number<T: FromStr>(field: &str) -> Result<T, String> {
+    field.parse().map_err(|_| format!("bad number: {}", field))
+}
+
+// `level:cells_per_frame` pairs joined by commas, so the curve stays one field
+fn encode_gravity(gravity: &Gravity) -> String {
+    gravity.levels
+        .iter()
+        .map(|(level, cells_per_frame)| format!("{}:{}", level, cells_per_frame))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn decode_gravity(field: &str) -> Result<Gravity, String> {
+    field.replace(',', "\n").replace(':', " ").parse()
+}
+
+fn player_number(field: &str) -> Result<usize, String> {
+    match number(field)? {
+        player if player < PLAYERS => Ok(player),
+        player => Err(format!("no player {}", player)),
+    }
+}
+
+// locked tiles only, the falling tetrad and its shadow stay private
+pub fn encode_board(board: &Grid<Tile>) -> Vec<String> {
+    board.grid.genrows()
+        .into_iter()
+        .map(|row| row.iter().map(tetrad::tile_code).collect())
+        .collect()
+}
+
+pub fn decode_board(rows: &[String], width: usize, height: usize) -> Grid<Tile> {
+    let mut tiles = Vec::new();
+    for row in 0..height {
+        let codes: Vec<char> = rows.get(row).map(|row| row.chars().collect()).unwrap_or_default();
+        for column in 0..width {
+            let mut tile = match codes.get(column) {
+                None | Some('.') => Tile::default(),
+                Some('G') => garbage::garbage_tile(),
+                Some(code) => match PieceSet::standard().piece(&code.to_string()) {
+                    Some(tetrad) => Tile { empty: false, ..tetrad.tiles[0] },
+                    None => garbage::garbage_tile(),
+                },
+            };
+            tile.row = row as isize;
+            tile.column = column as isize;
+            tiles.push(tile);
+        }
+    }
+    Grid::new(width, height, tiles)
+}
+
+// the other side's game as far as its messages tell
+#[derive(Clone, Debug)]
+pub struct RemoteBoard {
+    pub board: Grid<Tile>,
+    pub score: usize,
+    pub lines: usize,
+    pub garbage: usize,
+    pub game_over: bool,
+}
+
+impl RemoteBoard {
+
+    pub fn new(width: usize, height: usize) -> RemoteBoard {
+        RemoteBoard {
+            board: decode_board(&[], width, height),
+            score: 0,
+            lines: 0,
+            garbage: 0,
+            game_over: false,
+        }
+    }
+
+    // applies a lock or game over message, anything else is ignored
+    pub fn update(&mut self, message: &Message) {
+        match message {
+            Message::Lock { score, lines, garbage, rows, .. } => {
+                self.board = decode_board(rows, self.board.width, self.board.height);
+                self.score = *score;
+                self.lines = *lines;
+                self.garbage = *garbage;
+            },
+            Message::GameOver { .. } => self.game_over = true,
+            _ => (),
+        }
+    }
+
+    // what a renderer needs, the queue and held tetrad are never sent
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            board: self.board.clone(),
+            next: Vec::new(),
+            held: None,
+            preview_size: PieceSet::standard().preview_size(),
+            score: self.score,
+            lines: self.lines,
+            level: 1,
+            last_clear: None,
+            frames: 0,
+            game_over: self.game_over,
+            mode: Mode::Versus,
+            timer: None,
+            splits: Vec::new(),
+            finish: None,
+            garbage: self.garbage,
+        }
+    }
+}
+
+// a line based message stream that never blocks on reads
+pub struct Connection {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    inbox: VecDeque<Message>,
+    closed: bool,
+}
+
+impl Connection {
+
+    pub fn new(stream: TcpStream) -> io::Result<Connection> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Connection { stream, buffer: Vec::new(), inbox: VecDeque::new(), closed: false })
+    }
+
+    pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Connection> {
+        Connection::new(TcpStream::connect(address)?)
+    }
+
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        let line = format!("{}\n", message.encode());
+        let mut bytes = line.as_bytes();
+        while !bytes.is_empty() {
+            match self.stream.write(bytes) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n_bytes) => bytes = &bytes[n_bytes..],
+                Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(Duration::from_millis(1));
+                },
+                Err(error) if error.kind() == ErrorKind::Interrupted => (),
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    // every message that has arrived so far, an error once the other side is gone
+    pub fn receive(&mut self) -> io::Result<Vec<Message>> {
+        self.fill()?;
+        if self.inbox.is_empty() && self.closed {
+            return Err(io::Error::new(ErrorKind::ConnectionAborted, "connection closed"))
+        }
+        Ok(self.inbox.drain(..).collect())
+    }
+
+    // blocks until the next message, for handshakes
+    pub fn wait(&mut self, timeout: Duration) -> io::Result<Message> {
+        let start = Instant::now();
+        loop {
+            self.fill()?;
+            if let Some(message) = self.inbox.pop_front() {
+                return Ok(message)
+            }
+            if self.closed {
+                return Err(io::Error::new(ErrorKind::ConnectionAborted, "connection closed"))
+            }
+            if start.elapsed() >= timeout {
+                return Err(io::Error::new(ErrorKind::TimedOut, "no answer"))
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let mut chunk = [0; 4096];
+        while !self.closed {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => self.closed = true,
+                Ok(n_bytes) => self.buffer.extend_from_slice(&chunk[..n_bytes]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => (),
+                Err(error) => return Err(error),
+            }
+        }
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let message = Message::decode(String::from_utf8_lossy(&line).trim())
+                .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+            self.inbox.push_back(message);
+        }
+        Ok(())
+    }
+}
+
+// listens for the opponent and any number of spectators
+pub struct Host {
+    pub listener: TcpListener,
+    pub settings: Settings,
+    pub opponent: Option<Connection>,
+    pub spectators: Vec<Connection>,
+    // connected but no hello yet
+    arrivals: Vec<Connection>,
+}
+
+impl Host {
+
+    pub fn bind<A: ToSocketAddrs>(address: A, settings: Settings) -> io::Result<Host> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        // the welcome doesn't carry a piece set, both sides play the standard one
+        let settings = Settings { mode: Mode::Versus, pieces: PieceSet::default(), ..settings };
+        Ok(Host { listener, settings, opponent: None, spectators: Vec::new(), arrivals: Vec::new() })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // takes in new connections and answers their hello, never blocks
+    pub fn accept(&mut self) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => self.arrivals.push(Connection::new(stream)?),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        for mut connection in std::mem::take(&mut self.arrivals) {
+            // whoever hangs up or talks nonsense before saying hello is dropped
+            let hello = match connection.receive() {
+                Ok(messages) => messages.into_iter().next(),
+                Err(_) => continue,
+            };
+            let refusal = match hello {
+                None => {
+                    self.arrivals.push(connection);
+                    continue
+                },
+                Some(Message::Hello { version, .. }) if version != PROTOCOL_VERSION =>
+                    format!("protocol version {} required", PROTOCOL_VERSION),
+                Some(Message::Hello { role: Role::Player, .. }) if self.opponent.is_some() =>
+                    "the game is full".to_string(),
+                Some(Message::Hello { role, .. }) => {
+                    if connection.send(&self.welcome()).is_ok() {
+                        match role {
+                            Role::Player => self.opponent = Some(connection),
+                            Role::Spectator => self.spectators.push(connection),
+                        }
+                    }
+                    continue
+                },
+                Some(_) => "expected hello".to_string(),
+            };
+            let _ = connection.send(&Message::Refused { reason: refusal });
+        }
+        Ok(())
+    }
+
+    fn welcome(&self) -> Message {
+        Message::Welcome {
+            version: PROTOCOL_VERSION,
+            seed: self.settings.seed,
+            randomizer: self.settings.randomizer,
+            width: self.settings.width,
+            height: self.settings.height,
+            previews: self.settings.previews,
+            lock_delay: self.settings.lock_delay,
+            spawn_delay: self.settings.spawn_delay,
+            gravity: self.settings.gravity.clone(),
+        }
+    }
+
+    // spectators that stopped listening are dropped
+    fn tell_spectators(&mut self, message: &Message) {
+        self.spectators.retain_mut(|spectator| spectator.send(message).is_ok());
+    }
+}
+
+pub enum Link {
+    Host(Host),
+    Guest(Connection),
+}
+
+impl Link {
+
+    fn opponent(&mut self) -> io::Result<&mut Connection> {
+        match self {
+            Link::Host(host) => host.opponent.as_mut()
+                .ok_or_else(|| io::Error::new(ErrorKind::NotConnected, "no opponent yet")),
+            Link::Guest(connection) => Ok(connection),
+        }
+    }
+
+    // the opponent, and the spectators when hosting
+    fn publish(&mut self, message: &Message) -> io::Result<()> {
+        if let Link::Host(host) = self {
+            host.tell_spectators(message);
+        }
+        self.opponent()?.send(message)
+    }
+}
+
+// one side of a networked versus game: the local game plus the opponent as reported
+pub struct NetVersus {
+    pub game: Game,
+    pub player: usize,
+    pub opponent: RemoteBoard,
+    pub link: Link,
+    pieces: usize,
+}
+
+impl NetVersus {
+
+    // the host needs an opponent before the game can start
+    pub fn host(host: Host) -> io::Result<NetVersus> {
+        if host.opponent.is_none() {
+            return Err(io::Error::new(ErrorKind::NotConnected, "no opponent yet"))
+        }
+        let settings = host.settings.clone();
+        Ok(NetVersus::new(settings, HOST, Link::Host(host)))
+    }
+
+    pub fn join<A: ToSocketAddrs>(address: A) -> io::Result<NetVersus> {
+        let (connection, settings) = handshake(address, Role::Player)?;
+        Ok(NetVersus::new(settings, GUEST, Link::Guest(connection)))
+    }
+
+    fn new(settings: Settings, player: usize, link: Link) -> NetVersus {
+        let opponent = RemoteBoard::new(settings.width, settings.height);
+        NetVersus { game: Game::new(settings), player, opponent, link, pieces: 0 }
+    }
+
+    pub fn step(&mut self, inputs: &[Action]) -> io::Result<()> {
+        if let Link::Host(host) = &mut self.link {
+            host.accept()?;
+        }
+        if self.finished() {
+            return Ok(())
+        }
+
+        self.game.step(inputs);
+        if self.game.pieces != self.pieces {
+            self.pieces = self.game.pieces;
+            let lock = Message::Lock {
+                player: self.player,
+                score: self.game.score,
+                lines: self.game.lines,
+                garbage: self.game.garbage.total(),
+                rows: encode_board(&self.game.board.visible()),
+            };
+            self.link.publish(&lock)?;
+        }
+        let attack = self.game.take_attack();
+        if attack > 0 {
+            self.link.opponent()?.send(&Message::Garbage { lines: attack })?;
+        }
+        if self.game.game_over {
+            self.link.publish(&Message::GameOver { player: self.player })?;
+        }
+
+        for message in self.link.opponent()?.receive()? {
+            match message {
+                Message::Garbage { lines } => self.game.receive_garbage(lines),
+                Message::Lock { .. } | Message::GameOver { .. } => {
+                    self.opponent.update(&message);
+                    if let Link::Host(host) = &mut self.link {
+                        host.tell_spectators(&message);
+                    }
+                },
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finished(&self) -> bool {
+        self.game.game_over || self.opponent.game_over
+    }
+
+    pub fn winner(&self) -> Option<usize> {
+        match (self.game.game_over, self.opponent.game_over) {
+            (false, true) => Some(self.player),
+            (true, false) => Some(1 - self.player),
+            _ => None,
+        }
+    }
+}
+
+// watches a game without taking part in it
+pub struct Spectator {
+    pub connection: Connection,
+    pub players: Vec<RemoteBoard>,
+}
+
+impl Spectator {
+
+    pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Spectator> {
+        let (connection, settings) = handshake(address, Role::Spectator)?;
+        let players = vec![RemoteBoard::new(settings.width, settings.height); PLAYERS];
+        Ok(Spectator { connection, players })
+    }
+
+    pub fn update(&mut self) -> io::Result<()> {
+        for message in self.connection.receive()? {
+            match &message {
+                Message::Lock { player, .. } | Message::GameOver { player } =>
+                    self.players[*player].update(&message),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finished(&self) -> bool {
+        self.players.iter().any(|player| player.game_over)
+    }
+}
+
+// says hello and turns the host's welcome into the game settings
+fn handshake<A: ToSocketAddrs>(address: A, role: Role) -> io::Result<(Connection, Settings)> {
+    let mut connection = Connection::connect(address)?;
+    connection.send(&Message::Hello { version: PROTOCOL_VERSION, role })?;
+    match connection.wait(HANDSHAKE_TIMEOUT)? {
+        Message::Welcome { version, seed, randomizer, width, height, previews, lock_delay, spawn_delay, gravity }
+            if version == PROTOCOL_VERSION =>
+        {
+            let settings = Settings {
+                seed,
+                randomizer,
+                width,
+                height,
+                previews,
+                lock_delay,
+                spawn_delay,
+                gravity,
+                mode: Mode::Versus,
+                ..Default::default()
+            };
+            settings.check().map_err(|error| {
+                io::Error::new(ErrorKind::InvalidData, format!("the host's board can't be played: {}", error))
+            })?;
+            Ok((connection, settings))
+        },
+        Message::Refused { reason } => Err(io::Error::new(ErrorKind::ConnectionRefused, reason)),
+        _ => Err(io::Error::new(ErrorKind::InvalidData, "unexpected answer from the host")),
+    }
+}
@@ -0,0 +1,357 @@
+extern crate serde;
+extern crate toml;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
+use serde::Deserialize;
+
+use crate::grid::rgb::RGB;
+use crate::grid::grid::colored_char;
+
+use super::tile::{Tile, OUTLINED_SQUARE};
+use super::srs::Rotation;
+
+// a piece turns inside a square box at most this wide, so every rotation fits the bot's masks
+pub const MAX_PIECE_SIZE: usize = 8;
+
+// the guideline tetrominoes with srs kicks, used unless another set is loaded
+const STANDARD_DATA: &str = include_str!("tetrominoes.toml");
+
+// kicks out of each rotation, counter clockwise then clockwise
+pub type Kicks = [[Vec<(isize, isize)>; 2]; 4];
+
+// what every piece of one kind shares
+#[derive(Debug)]
+pub struct PieceKind {
+    pub name: String,
+    pub color: RGB,
+    // (row, column) of each cell in the spawn rotation, from the top left of the rotation box
+    pub cells: Vec<(isize, isize)>,
+    // (row, column) the box turns around, doubled so a cell corner is a whole number
+    pub center: (isize, isize),
+    // top left corner of the rotation box before spawn_position moves it into the buffer,
+    // the column counted from the middle of the board
+    pub spawn: (isize, isize),
+    pub kicks: Kicks,
+    // side of the smallest box at the top left that holds every rotation
+    pub size: usize,
+}
+
+impl PieceKind {
+
+    // the cells turned a quarter clockwise or counter clockwise around the center
+    fn turn(&self, cells: &[(isize, isize)], clockwise: bool) -> Vec<(isize, isize)> {
+        let (center_row, center_column) = self.center;
+        cells.iter()
+            .map(|(row, column)| {
+                let row = 2 * row - center_row;
+                let column = 2 * column - center_column;
+                let (row, column) = if clockwise { (column, -row) } else { (-column, row) };
+                ((row + center_row) / 2, (column + center_column) / 2)
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Piece {
+    pub tiles: Vec<Tile>,
+    pub rotation: Rotation,
+    // top left corner of the rotation box, which may hang off the board
+    pub origin: (isize, isize),
+    pub kind: Arc<PieceKind>,
+    pub name: String,
+}
+
+impl Piece {
+
+    pub fn new(kind: &Arc<PieceKind>) -> Piece {
+        let (row, column) = kind.spawn;
+        let tiles = kind.cells.iter()
+            .map(|cell| Tile { empty: true, color: kind.color, utf8: OUTLINED_SQUARE, row: row + cell.0, column: column + cell.1 })
+            .collect();
+        Piece {
+            tiles,
+            rotation: Rotation::Zero,
+            origin: kind.spawn,
+            kind: kind.clone(),
+            name: kind.name.clone(),
+        }
+    }
+
+    pub fn shift(&mut self, rows: isize, columns: isize) {
+        for tile in self.tiles.iter_mut() {
+            tile.row += rows;
+            tile.column += columns;
+        }
+        self.origin.0 += rows;
+        self.origin.1 += columns;
+    }
+
+    // turns around the kind's center and then applies a kick
+    pub fn rotate(&self, clockwise: bool, kick: (isize, isize)) -> Piece {
+        let origin = (self.origin.0 - kick.1, self.origin.1 + kick.0);
+        let cells: Vec<(isize, isize)> = self.tiles.iter()
+            .map(|tile| (tile.row - self.origin.0, tile.column - self.origin.1))
+            .collect();
+        let mut rotated = self.clone();
+        for (tile, (row, column)) in rotated.tiles.iter_mut().zip(self.kind.turn(&cells, clockwise)) {
+            tile.row = row + origin.0;
+            tile.column = column + origin.1;
+        }
+        rotated.origin = origin;
+        rotated.rotation = if clockwise {
+            self.rotation.clockwise()
+        } else {
+            self.rotation.counter_clockwise()
+        };
+        rotated
+    }
+
+    // the kicks to try, in order, turning from the current rotation
+    pub fn kicks(&self, clockwise: bool) -> &[(isize, isize)] {
+        &self.kind.kicks[self.rotation.index()][clockwise as usize]
+    }
+
+    pub fn get_position(&self) -> Vec<(isize, isize)> {
+        self.tiles.iter().map(|tile| (tile.row, tile.column)).collect()
+    }
+
+    // (rows, columns) the cells span as the piece is turned now
+    pub fn extent(&self) -> (usize, usize) {
+        extent(&self.get_position())
+    }
+}
+
+fn extent(cells: &[(isize, isize)]) -> (usize, usize) {
+    let span = |values: Vec<isize>| {
+        let low = values.iter().min().copied().unwrap_or(0);
+        let high = values.iter().max().copied().unwrap_or(-1);
+        (high - low + 1) as usize
+    };
+    (span(cells.iter().map(|cell| cell.0).collect()), span(cells.iter().map(|cell| cell.1).collect()))
+}
+
+// a piece as it is turned, centered in a box of (rows, columns) cells, or the empty box.
+// one line per row, padded to the full width so boxes stacked in a panel line up.
+// a piece bigger than the box gets as much room as it needs
+pub fn preview(piece: Option<&Piece>, size: (usize, usize)) -> String {
+    let cells = piece.map(Piece::get_position).unwrap_or_default();
+    let (height, width) = extent(&cells);
+    let (rows, columns) = (size.0.max(height), size.1.max(width));
+    let top = cells.iter().map(|cell| cell.0).min().unwrap_or(0) - ((rows - height) / 2) as isize;
+    let left = cells.iter().map(|cell| cell.1).min().unwrap_or(0) - ((columns - width) / 2) as isize;
+    let character = piece.map(|piece| colored_char(OUTLINED_SQUARE, piece.kind.color));
+    let mut lines = Vec::new();
+    for row in top..top + rows as isize {
+        let mut line = String::new();
+        for column in left..left + columns as isize {
+            match &character {
+                Some(character) if cells.contains(&(row, column)) => line.push_str(character),
+                _ => line.push_str("  "),
+            }
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+// the pieces a game draws from, in the order randomizers index them
+#[derive(Clone, Debug)]
+pub struct PieceSet {
+    pub kinds: Vec<Arc<PieceKind>>,
+    // the file the set was read from, replays carry it so they play back anywhere
+    pub data: String,
+}
+
+impl PartialEq for PieceSet {
+    fn eq(&self, other: &PieceSet) -> bool {
+        self.data == other.data
+    }
+}
+
+impl Default for PieceSet {
+    fn default() -> PieceSet {
+        PieceSet::standard().clone()
+    }
+}
+
+impl PieceSet {
+
+    // parsed once and shared
+    pub fn standard() -> &'static PieceSet {
+        static STANDARD: OnceLock<PieceSet> = OnceLock::new();
+        STANDARD.get_or_init(|| STANDARD_DATA.parse().expect("the standard piece set is valid"))
+    }
+
+    pub fn load(path: &Path) -> io::Result<PieceSet> {
+        fs::read_to_string(path)
+            .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", path.display(), error)))?
+            .parse()
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), error)))
+    }
+
+    pub fn is_standard(&self) -> bool {
+        self.data == STANDARD_DATA
+    }
+
+    // the box every preview of the set is drawn in, big enough for any piece as it spawns
+    pub fn preview_size(&self) -> (usize, usize) {
+        self.kinds.iter()
+            .map(|kind| extent(&kind.cells))
+            .fold((0, 0), |(rows, columns), (height, width)| (rows.max(height), columns.max(width)))
+    }
+
+    // every piece has to spawn inside the board, so none may be wider than it
+    pub fn check_width(&self, width: usize) -> Result<(), String> {
+        for kind in self.kinds.iter() {
+            let left = kind.spawn.1 + width as isize / 2;
+            let inside = kind.cells.iter().all(|(_, column)| (0..width as isize).contains(&(left + column)));
+            if !inside {
+                return Err(format!("{} spawns off a board {} wide, spawn columns count from the middle", kind.name, width))
+            }
+        }
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.kinds.iter().map(|kind| kind.name.clone()).collect()
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.kinds.iter().position(|kind| kind.name == name)
+    }
+
+    // a new piece of the named kind, spawn_position puts it on a board
+    pub fn piece(&self, name: &str) -> Option<Piece> {
+        self.index(name).map(|index| Piece::new(&self.kinds[index]))
+    }
+}
+
+// the file as written: kick tables by name and the pieces in order.
+// kicks are [x, y] with x to the right and y up, keyed by the rotations
+// a turn goes between, 0 R 2 L, as in "0R" for spawn to right
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SetFile {
+    #[serde(default)]
+    kicks: HashMap<String, HashMap<String, Vec<[isize; 2]>>>,
+    piece: Vec<PieceFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PieceFile {
+    name: String,
+    color: [u8; 3],
+    // [row, column] in the rotation box
+    cells: Vec<[isize; 2]>,
+    // [row, column], on a cell or on a corner between cells
+    center: [f64; 2],
+    // [row, column] of the box's top left corner, the column from the middle of the board
+    spawn: [isize; 2],
+    // a table from [kicks], without one the piece only turns in place
+    kicks: Option<String>,
+}
+
+const ROTATION_NAMES: [&str; 4] = ["0", "R", "2", "L"];
+
+// a table's kicks indexed like PieceKind::kicks
+fn kick_table(name: &str, table: &HashMap<String, Vec<[isize; 2]>>) -> Result<Kicks, String> {
+    let turn_name = |from: usize, clockwise: bool| {
+        let to = if clockwise { (from + 1) % 4 } else { (from + 3) % 4 };
+        format!("{}{}", ROTATION_NAMES[from], ROTATION_NAMES[to])
+    };
+    if let Some(unknown) = table.keys().find(|key| (0..4).all(|from| **key != turn_name(from, false) && **key != turn_name(from, true))) {
+        return Err(format!("kick table {} has an unknown turn: {}", name, unknown))
+    }
+    let mut kicks: Kicks = Default::default();
+    for (from, pair) in kicks.iter_mut().enumerate() {
+        for (clockwise, turn) in pair.iter_mut().enumerate() {
+            let turn_name = turn_name(from, clockwise == 1);
+            let listed = table.get(&turn_name)
+                .ok_or_else(|| format!("kick table {} is missing {}", name, turn_name))?;
+            if listed.is_empty() {
+                return Err(format!("kick table {} has no kicks for {}", name, turn_name))
+            }
+            *turn = listed.iter().map(|kick| (kick[0], kick[1])).collect();
+        }
+    }
+    Ok(kicks)
+}
+
+fn piece_kind(file: PieceFile, tables: &HashMap<String, Kicks>) -> Result<PieceKind, String> {
+    let name = file.name;
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        return Err(format!("piece names can't be empty or have spaces: `{}`", name))
+    }
+    if file.cells.is_empty() {
+        return Err(format!("piece {} has no cells", name))
+    }
+    let doubled = |half: f64| (half * 2.).round() as isize;
+    let center = (doubled(file.center[0]), doubled(file.center[1]));
+    let whole = file.center.iter().all(|half| (half * 2.).fract() == 0.);
+    if !whole || center.0 % 2 != center.1 % 2 {
+        return Err(format!("piece {} has to turn around a cell or a cell corner", name))
+    }
+    let kicks = match &file.kicks {
+        Some(table) => tables.get(table).cloned().ok_or_else(|| format!("piece {} uses an unknown kick table: {}", name, table))?,
+        None => Default::default(),
+    };
+    // a piece without a table turns in place or not at all
+    let kicks = kicks.map(|pair| pair.map(|turn| if turn.is_empty() { vec![(0, 0)] } else { turn }));
+    let mut kind = PieceKind {
+        name,
+        color: RGB { r: file.color[0], g: file.color[1], b: file.color[2] },
+        cells: file.cells.iter().map(|cell| (cell[0], cell[1])).collect(),
+        center,
+        spawn: (file.spawn[0], file.spawn[1]),
+        kicks,
+        size: 0,
+    };
+    let mut cells = kind.cells.clone();
+    cells.sort_unstable();
+    cells.dedup();
+    if cells.len() != kind.cells.len() {
+        return Err(format!("piece {} lists a cell twice", kind.name))
+    }
+    for _ in 0..4 {
+        for (row, column) in cells.iter() {
+            if *row < 0 || *column < 0 || row.max(column) + 1 > MAX_PIECE_SIZE as isize {
+                return Err(format!("piece {} has to stay in a {} by {} box at the top left as it turns", kind.name, MAX_PIECE_SIZE, MAX_PIECE_SIZE))
+            }
+            kind.size = kind.size.max(*row.max(column) as usize + 1);
+        }
+        cells = kind.turn(&cells, true);
+    }
+    Ok(kind)
+}
+
+impl FromStr for PieceSet {
+    type Err = String;
+
+    fn from_str(data: &str) -> Result<PieceSet, String> {
+        let file: SetFile = toml::from_str(data).map_err(|error| error.to_string())?;
+        let mut tables = HashMap::new();
+        for (name, table) in file.kicks.iter() {
+            tables.insert(name.clone(), kick_table(name, table)?);
+        }
+        let mut kinds: Vec<Arc<PieceKind>> = Vec::new();
+        for piece in file.piece {
+            let kind = piece_kind(piece, &tables)?;
+            if kinds.iter().any(|known| known.name == kind.name) {
+                return Err(format!("two pieces are named {}", kind.name))
+            }
+            kinds.push(Arc::new(kind));
+        }
+        if kinds.is_empty() {
+            return Err("a piece set needs at least one piece".to_string())
+        }
+        Ok(PieceSet { kinds, data: data.to_string() })
+    }
+}
@@ -0,0 +1,143 @@
+extern crate rand;
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+// picks the next tetrad as an index into the piece set's names,
+// drawing all randomness from the queue's seeded rng
+pub trait Randomizer {
+
+    fn next_index(&mut self, rng: &mut StdRng, names: &[String]) -> usize;
+}
+
+// deals every tetrad `copies` times before shuffling a new bag
+pub struct Bag {
+    copies: usize,
+    bag: Vec<usize>,
+}
+
+impl Bag {
+
+    pub fn new(copies: usize) -> Bag {
+        Bag { copies, bag: Vec::new() }
+    }
+}
+
+impl Randomizer for Bag {
+
+    fn next_index(&mut self, rng: &mut StdRng, names: &[String]) -> usize {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies {
+                self.bag.extend(0..names.len());
+            }
+            self.bag.shuffle(rng);
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+pub struct Random;
+
+impl Randomizer for Random {
+
+    fn next_index(&mut self, rng: &mut StdRng, names: &[String]) -> usize {
+        rng.gen_range(0, names.len())
+    }
+}
+
+// TGM style: reroll a tetrad that is in the recent history, a limited number of times
+pub struct History {
+    history: VecDeque<usize>,
+    // rerolls after the first draw
+    rolls: usize,
+    first: bool,
+}
+
+impl History {
+
+    pub fn new(rolls: usize) -> History {
+        History { history: VecDeque::new(), rolls, first: true }
+    }
+}
+
+impl Randomizer for History {
+
+    fn next_index(&mut self, rng: &mut StdRng, names: &[String]) -> usize {
+        let named = |wanted: &[&str]| -> Vec<usize> {
+            wanted.iter().filter_map(|name| names.iter().position(|known| known == name)).collect()
+        };
+        let index = if self.first {
+            // the history starts as S and Z and the first tetrad is never an S, Z or O,
+            // a set without I, T, J or L starts with anything
+            self.first = false;
+            self.history = named(&["Z", "S", "S", "Z"]).into();
+            let starts = named(&["I", "T", "J", "L"]);
+            match starts.choose(rng) {
+                Some(index) => *index,
+                None => rng.gen_range(0, names.len()),
+            }
+        } else {
+            let mut index = rng.gen_range(0, names.len());
+            for _ in 0..self.rolls {
+                if !self.history.contains(&index) {
+                    break
+                }
+                index = rng.gen_range(0, names.len());
+            }
+            index
+        };
+        // four tetrads, a set without S or Z fills its history as it plays
+        if self.history.len() == 4 {
+            self.history.pop_front();
+        }
+        self.history.push_back(index);
+        index
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RandomizerKind {
+    SevenBag,
+    FourteenBag,
+    Random,
+    History,
+}
+
+impl RandomizerKind {
+
+    pub fn build(self) -> Box<dyn Randomizer> {
+        match self {
+            RandomizerKind::SevenBag => Box::new(Bag::new(1)),
+            RandomizerKind::FourteenBag => Box::new(Bag::new(2)),
+            RandomizerKind::Random => Box::new(Random),
+            RandomizerKind::History => Box::new(History::new(6)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RandomizerKind::SevenBag => "7bag",
+            RandomizerKind::FourteenBag => "14bag",
+            RandomizerKind::Random => "random",
+            RandomizerKind::History => "history",
+        }
+    }
+}
+
+impl FromStr for RandomizerKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<RandomizerKind, String> {
+        match name {
+            "7bag" => Ok(RandomizerKind::SevenBag),
+            "14bag" => Ok(RandomizerKind::FourteenBag),
+            "random" => Ok(RandomizerKind::Random),
+            "history" => Ok(RandomizerKind::History),
+            _ => Err(format!("unknown randomizer: {}", name)),
+        }
+    }
+}
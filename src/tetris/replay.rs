@@ -0,0 +1,266 @@
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use super::game::{Action, Game, Settings};
+use super::gravity::Gravity;
+use super::piece::PieceSet;
+
+const MAGIC: &[u8; 4] = b"GWSR";
+// bump the version whenever the layout changes, other versions are refused
+const VERSION: u8 = 1;
+
+const ACTIONS: [Action; 7] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::RotateLeft,
+    Action::RotateRight,
+    Action::Hold,
+];
+
+// the settings and seed of a game plus every input, stamped with the frame it was applied on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub settings: Settings,
+    pub events: Vec<(u64, Action)>,
+    // length of the recording in frames
+    pub frames: u64,
+}
+
+impl Replay {
+
+    pub fn new(settings: Settings) -> Replay {
+        Replay { settings, events: Vec::new(), frames: 0 }
+    }
+
+    pub fn record(&mut self, frame: u64, action: Action) {
+        self.events.push((frame, action));
+        self.frames = self.frames.max(frame);
+    }
+
+    pub fn finish(&mut self, frames: u64) {
+        self.frames = self.frames.max(frames);
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let settings = &self.settings;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&settings.seed.to_le_bytes());
+        let randomizer = settings.randomizer.name().as_bytes();
+        bytes.push(randomizer.len() as u8);
+        bytes.extend_from_slice(randomizer);
+        let mode = settings.mode.name().as_bytes();
+        bytes.push(mode.len() as u8);
+        bytes.extend_from_slice(mode);
+        write_varint(&mut bytes, settings.width as u64);
+        write_varint(&mut bytes, settings.height as u64);
+        write_varint(&mut bytes, settings.lock_delay.as_millis() as u64);
+        write_varint(&mut bytes, settings.spawn_delay.as_millis() as u64);
+        // the whole piece set file, left empty for the standard set
+        let pieces = if settings.pieces.is_standard() { "" } else { settings.pieces.data.as_str() };
+        write_varint(&mut bytes, pieces.len() as u64);
+        bytes.extend_from_slice(pieces.as_bytes());
+        write_varint(&mut bytes, settings.previews as u64);
+        write_varint(&mut bytes, settings.gravity.levels.len() as u64);
+        for (level, cells_per_frame) in settings.gravity.levels.iter() {
+            write_varint(&mut bytes, *level as u64);
+            bytes.extend_from_slice(&cells_per_frame.to_bits().to_le_bytes());
+        }
+        write_varint(&mut bytes, self.frames);
+        write_varint(&mut bytes, self.events.len() as u64);
+        // frames are stored as the gap since the previous input
+        let mut last_frame = 0;
+        for (frame, action) in self.events.iter() {
+            write_varint(&mut bytes, frame - last_frame);
+            bytes.push(ACTIONS.iter().position(|known| known == action).unwrap() as u8);
+            last_frame = *frame;
+        }
+        writer.write_all(&bytes)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Replay> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut bytes = bytes.into_iter();
+
+        let magic: Vec<u8> = bytes.by_ref().take(4).collect();
+        if magic != MAGIC {
+            return Err(invalid("not a replay file"))
+        }
+        let version = next_byte(&mut bytes)?;
+        if version != VERSION {
+            return Err(invalid("unsupported replay version"))
+        }
+        let seed = u64::from_le_bytes(read_array(&mut bytes)?);
+        let randomizer = read_name(&mut bytes)?
+            .parse()
+            .map_err(|error: String| invalid(&error))?;
+        let mode = read_name(&mut bytes)?
+            .parse()
+            .map_err(|error: String| invalid(&error))?;
+        let width = read_varint(&mut bytes)? as usize;
+        let height = read_varint(&mut bytes)? as usize;
+        let lock_delay = Duration::from_millis(read_varint(&mut bytes)?);
+        let spawn_delay = Duration::from_millis(read_varint(&mut bytes)?);
+        let length = read_varint(&mut bytes)? as usize;
+        let data: Vec<u8> = bytes.by_ref().take(length).collect();
+        if data.len() != length {
+            return Err(invalid("replay ended early"))
+        }
+        let pieces = if length > 0 {
+            String::from_utf8(data)
+                .map_err(|_| invalid("bad piece set"))?
+                .parse()
+                .map_err(|error: String| invalid(&error))?
+        } else {
+            PieceSet::default()
+        };
+        let previews = read_varint(&mut bytes)? as usize;
+        let mut levels = Vec::new();
+        for _ in 0..read_varint(&mut bytes)? {
+            let level = read_varint(&mut bytes)? as usize;
+            let cells_per_frame = f64::from_bits(u64::from_le_bytes(read_array(&mut bytes)?));
+            levels.push((level, cells_per_frame));
+        }
+        let frames = read_varint(&mut bytes)?;
+        let mut events = Vec::new();
+        let mut frame: u64 = 0;
+        for _ in 0..read_varint(&mut bytes)? {
+            frame = frame.checked_add(read_varint(&mut bytes)?).ok_or_else(|| invalid("bad frame"))?;
+            let action = ACTIONS.get(next_byte(&mut bytes)? as usize)
+                .ok_or_else(|| invalid("unknown action"))?;
+            events.push((frame, *action));
+        }
+
+        let settings = Settings {
+            width,
+            height,
+            seed,
+            randomizer,
+            lock_delay,
+            spawn_delay,
+            gravity: Gravity { levels },
+            mode,
+            pieces,
+            previews,
+        };
+        settings.check().map_err(|error| invalid(&error))?;
+        Ok(Replay { settings, events, frames })
+    }
+}
+
+// replays a recording frame by frame onto a fresh game
+pub struct Playback {
+    pub replay: Replay,
+    pub game: Game,
+    next_event: usize,
+}
+
+impl Playback {
+
+    pub fn new(replay: Replay) -> Playback {
+        let game = Game::new(replay.settings.clone());
+        Playback { replay, game, next_event: 0 }
+    }
+
+    // feeds the game the inputs recorded for the current frame
+    pub fn step(&mut self) {
+        if self.finished() {
+            return
+        }
+        let mut inputs = Vec::new();
+        while let Some((frame, action)) = self.replay.events.get(self.next_event) {
+            if *frame > self.game.frames {
+                break
+            }
+            inputs.push(*action);
+            self.next_event += 1;
+        }
+        self.game.step(&inputs);
+    }
+
+    pub fn finished(&self) -> bool {
+        let inputs_done = self.next_event >= self.replay.events.len();
+        self.game.game_over || (inputs_done && self.game.frames >= self.replay.frames)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn next_byte<I: Iterator<Item = u8>>(bytes: &mut I) -> io::Result<u8> {
+    bytes.next().ok_or_else(|| invalid("replay ended early"))
+}
+
+// a length byte followed by that many bytes of utf8
+fn read_name<I: Iterator<Item = u8>>(bytes: &mut I) -> io::Result<String> {
+    let length = next_byte(bytes)? as usize;
+    let name: Vec<u8> = bytes.by_ref().take(length).collect();
+    String::from_utf8(name).map_err(|_| invalid("bad name"))
+}
+
+fn read_array<I: Iterator<Item = u8>>(bytes: &mut I) -> io::Result<[u8; 8]> {
+    let mut array = [0; 8];
+    for byte in array.iter_mut() {
+        *byte = next_byte(bytes)?;
+    }
+    Ok(array)
+}
+
+// little endian base 128, seven bits per byte
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint<I: Iterator<Item = u8>>(bytes: &mut I) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = next_byte(bytes)?;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value)
+        }
+    }
+    Err(invalid("varint too long"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_written_replay_plays_back_the_same_game() {
+        let mut game = Game::new(Settings { seed: 7, ..Default::default() });
+        let mut replay = Replay::new(game.settings.clone());
+        let moves = [Action::MoveLeft, Action::RotateRight, Action::MoveRight, Action::Hold, Action::HardDrop];
+        while !game.game_over && game.frames < 3000 {
+            let inputs: Vec<Action> = match game.frames % 7 {
+                0 => vec![moves[(game.frames / 7) as usize % moves.len()]],
+                _ => Vec::new(),
+            };
+            for action in inputs.iter() {
+                replay.record(game.frames, *action);
+            }
+            game.step(&inputs);
+        }
+        replay.finish(game.frames);
+
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        let read = Replay::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, replay);
+        let mut playback = Playback::new(read);
+        while !playback.finished() {
+            playback.step();
+        }
+        assert_eq!(format!("{:?}", playback.game.snapshot()), format!("{:?}", game.snapshot()));
+    }
+}
@@ -0,0 +1,142 @@
+use super::input::Key;
+use super::mode::Mode;
+use super::highscore::MAX_NAME_LENGTH;
+
+// what the mode select screen offers, in order
+pub const MODES: [Mode; 5] = [Mode::Marathon, Mode::Sprint, Mode::Ultra, Mode::Zen, Mode::Versus];
+
+// keys read by the scenes while playing, so no action can be bound to them
+pub const RESERVED_KEYS: [Key; 3] = [Key::Char('p'), Key::Char('q'), Key::Escape];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PauseItem {
+    Resume,
+    Restart,
+    // drops the game and goes back to the title screen
+    Title,
+}
+
+pub const PAUSE_ITEMS: [PauseItem; 3] = [PauseItem::Resume, PauseItem::Restart, PauseItem::Title];
+
+impl PauseItem {
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PauseItem::Resume => "resume",
+            PauseItem::Restart => "restart",
+            PauseItem::Title => "quit to title",
+        }
+    }
+}
+
+// the screens of the game, the front end draws whichever one is current
+#[derive(Clone, Debug, PartialEq)]
+pub enum Scene {
+    Title,
+    // selected is an index into MODES
+    ModeSelect { selected: usize },
+    Playing,
+    // selected is an index into PAUSE_ITEMS
+    Paused { selected: usize },
+    GameOver,
+    HighScoreEntry { rank: usize, name: String },
+    Quit,
+}
+
+// what the front end has to do after a key changed the scene
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    // a new game in the mode, restarts included
+    Start(Mode),
+    // the clock stops while paused and the board is hidden
+    Pause,
+    Resume,
+    // the name typed in for the high score
+    Record(String),
+    Quit,
+}
+
+// the current scene and the transitions between scenes
+pub struct Scenes {
+    pub scene: Scene,
+    // the mode the next game is played in
+    pub mode: Mode,
+}
+
+fn previous(selected: usize, length: usize) -> usize {
+    (selected + length - 1) % length
+}
+
+fn next(selected: usize, length: usize) -> usize {
+    (selected + 1) % length
+}
+
+impl Scenes {
+
+    pub fn new(mode: Mode) -> Scenes {
+        Scenes { scene: Scene::Title, mode }
+    }
+
+    // the game ended, rank is where it landed in the high scores if it made them
+    pub fn game_over(&mut self, rank: Option<usize>) {
+        self.scene = match rank {
+            Some(rank) => Scene::HighScoreEntry { rank, name: String::new() },
+            None => Scene::GameOver,
+        };
+    }
+
+    // keys the game itself uses never get here while playing
+    pub fn press(&mut self, key: Key) -> Option<Command> {
+        let mode = self.mode;
+        let (scene, command) = match (&mut self.scene, key) {
+            (Scene::Title, Key::Enter) => (Scene::Playing, Some(Command::Start(mode))),
+            (Scene::Title, Key::Char('m')) => {
+                let selected = MODES.iter().position(|known| *known == mode).unwrap_or(0);
+                (Scene::ModeSelect { selected }, None)
+            },
+            (Scene::Title, Key::Char('q')) | (Scene::Title, Key::Escape) => (Scene::Quit, Some(Command::Quit)),
+
+            (Scene::ModeSelect { selected }, Key::Up) => (Scene::ModeSelect { selected: previous(*selected, MODES.len()) }, None),
+            (Scene::ModeSelect { selected }, Key::Down) => (Scene::ModeSelect { selected: next(*selected, MODES.len()) }, None),
+            (Scene::ModeSelect { selected }, Key::Enter) => {
+                self.mode = MODES[*selected];
+                (Scene::Title, None)
+            },
+            (Scene::ModeSelect { .. }, Key::Escape) => (Scene::Title, None),
+
+            (Scene::Playing, Key::Char('p')) | (Scene::Playing, Key::Escape) =>
+                (Scene::Paused { selected: 0 }, Some(Command::Pause)),
+            (Scene::Playing, Key::Char('q')) => (Scene::Quit, Some(Command::Quit)),
+
+            (Scene::Paused { selected }, Key::Up) => (Scene::Paused { selected: previous(*selected, PAUSE_ITEMS.len()) }, None),
+            (Scene::Paused { selected }, Key::Down) => (Scene::Paused { selected: next(*selected, PAUSE_ITEMS.len()) }, None),
+            (Scene::Paused { selected }, Key::Enter) => match PAUSE_ITEMS[*selected] {
+                PauseItem::Resume => (Scene::Playing, Some(Command::Resume)),
+                PauseItem::Restart => (Scene::Playing, Some(Command::Start(mode))),
+                PauseItem::Title => (Scene::Title, None),
+            },
+            (Scene::Paused { .. }, Key::Char('p')) | (Scene::Paused { .. }, Key::Escape) =>
+                (Scene::Playing, Some(Command::Resume)),
+            (Scene::Paused { .. }, Key::Char('q')) => (Scene::Quit, Some(Command::Quit)),
+
+            (Scene::GameOver, Key::Enter) => (Scene::Title, None),
+            (Scene::GameOver, Key::Char('r')) => (Scene::Playing, Some(Command::Start(mode))),
+            (Scene::GameOver, Key::Char('q')) => (Scene::Quit, Some(Command::Quit)),
+
+            (Scene::HighScoreEntry { name, .. }, Key::Enter) if !name.is_empty() =>
+                (Scene::GameOver, Some(Command::Record(name.clone()))),
+            (Scene::HighScoreEntry { name, .. }, Key::Backspace) => {
+                name.pop();
+                return None
+            },
+            (Scene::HighScoreEntry { name, .. }, Key::Char(character))
+                if !character.is_control() && name.chars().count() < MAX_NAME_LENGTH => {
+                name.push(character);
+                return None
+            },
+            _ => return None,
+        };
+        self.scene = scene;
+        command
+    }
+}
@@ -0,0 +1,92 @@
+// guideline scoring, every value is multiplied by the level
+
+// garbage lines added for consecutive clears, indexed by the combo
+const COMBO_ATTACK: [usize; 11] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clear {
+    pub lines: usize,
+    pub spin: Spin,
+    pub back_to_back: bool,
+    // consecutive clears before this one, 0 for the first clear of a chain
+    pub combo: usize,
+}
+
+impl Clear {
+
+    // tetrises and spins that clear lines keep a back to back chain going
+    pub fn difficult(&self) -> bool {
+        self.lines == 4 || (self.spin != Spin::None && self.lines > 0)
+    }
+
+    pub fn base_score(&self) -> usize {
+        match (self.spin, self.lines) {
+            (Spin::None, 0) => 0,
+            (Spin::None, 1) => 100,
+            (Spin::None, 2) => 300,
+            (Spin::None, 3) => 500,
+            (Spin::None, _) => 800,
+            (Spin::Mini, 0) => 100,
+            (Spin::Mini, 1) => 200,
+            (Spin::Mini, _) => 400,
+            (Spin::Full, 0) => 400,
+            (Spin::Full, 1) => 800,
+            (Spin::Full, 2) => 1200,
+            (Spin::Full, _) => 1600,
+        }
+    }
+
+    pub fn score(&self, level: usize) -> usize {
+        let mut score = self.base_score() * level;
+        if self.back_to_back {
+            score = score * 3 / 2;
+        }
+        score + 50 * self.combo * level
+    }
+
+    // garbage lines sent to the opponent by the guideline attack table
+    pub fn attack(&self) -> usize {
+        if self.lines == 0 {
+            return 0
+        }
+        let lines = match (self.spin, self.lines) {
+            (Spin::None, 1) => 0,
+            (Spin::None, 2) => 1,
+            (Spin::None, 3) => 2,
+            (Spin::None, _) => 4,
+            (Spin::Mini, 1) => 0,
+            (Spin::Mini, _) => 1,
+            (Spin::Full, 1) => 2,
+            (Spin::Full, 2) => 4,
+            (Spin::Full, _) => 6,
+        };
+        let back_to_back = if self.back_to_back { 1 } else { 0 };
+        lines + back_to_back + COMBO_ATTACK[self.combo.min(COMBO_ATTACK.len() - 1)]
+    }
+
+    pub fn name(&self) -> String {
+        let lines = match self.lines {
+            0 => "",
+            1 => "Single",
+            2 => "Double",
+            3 => "Triple",
+            _ => "Tetris",
+        };
+        let mut name = match self.spin {
+            Spin::None => lines.to_string(),
+            Spin::Mini => format!("Mini T-Spin {}", lines),
+            Spin::Full => format!("T-Spin {}", lines),
+        };
+        if self.back_to_back {
+            name = format!("B2B {}", name);
+        }
+        name.trim_end().to_string()
+    }
+}
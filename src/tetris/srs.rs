@@ -0,0 +1,40 @@
+// Super Rotation System rotation states, the kick tables come with each piece set
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    Zero,
+    Right,
+    Two,
+    Left,
+}
+
+impl Rotation {
+
+    pub fn clockwise(self) -> Rotation {
+        match self {
+            Rotation::Zero => Rotation::Right,
+            Rotation::Right => Rotation::Two,
+            Rotation::Two => Rotation::Left,
+            Rotation::Left => Rotation::Zero,
+        }
+    }
+
+    pub fn counter_clockwise(self) -> Rotation {
+        match self {
+            Rotation::Zero => Rotation::Left,
+            Rotation::Left => Rotation::Two,
+            Rotation::Two => Rotation::Right,
+            Rotation::Right => Rotation::Zero,
+        }
+    }
+
+    // clockwise quarter turns from spawn
+    pub fn index(self) -> usize {
+        match self {
+            Rotation::Zero => 0,
+            Rotation::Right => 1,
+            Rotation::Two => 2,
+            Rotation::Left => 3,
+        }
+    }
+}
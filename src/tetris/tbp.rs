@@ -0,0 +1,369 @@
+extern crate serde;
+extern crate serde_json;
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::board::Board;
+use super::tetrad;
+use super::srs::Rotation;
+use super::game::{self, Action, Game, Settings};
+use super::ai::{self, Controller};
+
+// Tetris Bot Protocol: one json message per line over the bot's stdin and stdout
+// bots expect a 10 wide board with 40 rows, row 0 at the bottom, and the standard tetrominoes
+pub const BOARD_WIDTH: usize = 10;
+// the visible rows, the buffer above them makes up the other 20
+pub const BOARD_HEIGHT: usize = 20;
+
+// how long a bot may take to answer before it is given up on
+const BOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Location {
+    #[serde(rename = "type")]
+    pub kind: String,
+    // north, east, south or west
+    pub orientation: String,
+    // the cell the tetrad rotates around, y counts up from the bottom row
+    pub x: isize,
+    pub y: isize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Move {
+    pub location: Location,
+    // none, mini or full
+    pub spin: String,
+}
+
+// what the bot sends
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info { name: String, version: String, author: String, features: Vec<String> },
+    Ready,
+    Error { reason: String },
+    // best first
+    Suggestion { moves: Vec<Move> },
+}
+
+// what the bot is sent
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules,
+    Start {
+        hold: Option<String>,
+        // the active tetrad first, then the previews
+        queue: Vec<String>,
+        combo: usize,
+        back_to_back: bool,
+        // rows from the bottom up, a cell is a tetrad name, G for garbage, or null
+        board: Vec<Vec<Option<String>>>,
+    },
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        play: Move,
+    },
+    NewPiece { piece: String },
+    Quit,
+}
+
+pub fn orientation(rotation: Rotation) -> &'static str {
+    match rotation {
+        Rotation::Zero => "north",
+        Rotation::Right => "east",
+        Rotation::Two => "south",
+        Rotation::Left => "west",
+    }
+}
+
+// cells around the rotation center facing north, x to the right and y up
+fn north_cells(name: &str) -> Option<[(isize, isize); 4]> {
+    match name {
+        "I" => Some([(-1, 0), (0, 0), (1, 0), (2, 0)]),
+        "O" => Some([(0, 0), (1, 0), (0, 1), (1, 1)]),
+        "T" => Some([(-1, 0), (0, 0), (1, 0), (0, 1)]),
+        "S" => Some([(-1, 0), (0, 0), (0, 1), (1, 1)]),
+        "Z" => Some([(-1, 1), (0, 1), (0, 0), (1, 0)]),
+        "J" => Some([(-1, 1), (-1, 0), (0, 0), (1, 0)]),
+        "L" => Some([(-1, 0), (0, 0), (1, 0), (1, 1)]),
+        _ => None,
+    }
+}
+
+// the (row, column) board cells a location covers
+pub fn location_cells(location: &Location, board: &Board) -> Option<Vec<(isize, isize)>> {
+    let turns = ["north", "east", "south", "west"].iter().position(|name| *name == location.orientation)?;
+    let cells = north_cells(&location.kind)?
+        .iter()
+        .map(|cell| (0..turns).fold(*cell, |(x, y), _| (y, -x)))
+        .map(|(x, y)| (board.height as isize - 1 - (location.y + y), location.x + x))
+        .collect();
+    Some(cells)
+}
+
+// games a bot can be handed, anything else it would misread
+pub fn check_settings(settings: &Settings) -> Result<(), String> {
+    if settings.width != BOARD_WIDTH || settings.height != BOARD_HEIGHT {
+        return Err(format!("bots play on a {}x{} board, not {}x{}", BOARD_WIDTH, BOARD_HEIGHT, settings.width, settings.height))
+    }
+    if !settings.pieces.is_standard() {
+        return Err("bots only know the standard tetrominoes".to_string())
+    }
+    Ok(())
+}
+
+fn board_rows(board: &Board) -> Vec<Vec<Option<String>>> {
+    let mut rows: Vec<Vec<Option<String>>> = board.view().grid.genrows()
+        .into_iter()
+        .map(|row| {
+            row.iter()
+                .map(tetrad::tile_code)
+                .map(|code| if code == '.' { None } else { Some(code.to_string()) })
+                .collect()
+        })
+        .collect();
+    rows.reverse();
+    rows
+}
+
+// the actions that carry out a move, starting with hold when it names the other tetrad
+pub fn move_actions(game: &Game, play: &Move) -> Option<Vec<Action>> {
+    let kind = &play.location.kind;
+    let (tetrad, mut actions) = if *kind == game.active_tetrad.name {
+        (game.active_tetrad.clone(), Vec::new())
+    } else {
+        let swapped = match &game.held_tetrad {
+            Some(name) => name.clone(),
+            None => game.previewed().next()?.name.clone(),
+        };
+        if !game.can_hold || swapped != *kind {
+            return None
+        }
+        let (tetrad, valid_spawn) = game::spawn_position(&game.board, game.settings.pieces.piece(kind)?);
+        if !valid_spawn {
+            return None
+        }
+        (tetrad, vec![Action::Hold])
+    };
+    let target = location_cells(&play.location, &game.board)?;
+    let spin = play.spin != "none";
+    let path = ai::path_to(&game.board, &tetrad, &target, spin)
+        .or_else(|| ai::path_to(&game.board, &tetrad, &target, false))?;
+    actions.extend(path);
+    Some(actions)
+}
+
+// tetrads drawn or in the previews, counted from the first draw
+fn previewed(game: &Game) -> usize {
+    game.queue.drawn + game.previewed().count()
+}
+
+// a move waiting for its lock: the move and the game's pieces, lines and garbage before it
+struct Pending {
+    play: Move,
+    pieces: usize,
+    lines: usize,
+    garbage: usize,
+}
+
+// a bot program started as a child process
+pub struct ExternalBot {
+    pub name: String,
+    pub version: String,
+    pub author: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    started: bool,
+    // tetrads the bot has been told about so far, counted from the first draw
+    revealed: usize,
+    pending: Option<Pending>,
+}
+
+impl ExternalBot {
+
+    // `command` is the program followed by its arguments, separated by spaces
+    pub fn launch(command: &str) -> io::Result<ExternalBot> {
+        let mut words = command.split_whitespace();
+        let program = words.next()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no bot command"))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // a thread does the blocking reads so a silent bot can time out
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let sent = line.map(|line| sender.send(line).is_ok());
+                if !sent.unwrap_or(false) {
+                    break
+                }
+            }
+        });
+
+        let mut bot = ExternalBot {
+            name: String::new(),
+            version: String::new(),
+            author: String::new(),
+            child,
+            stdin,
+            lines,
+            started: false,
+            revealed: 0,
+            pending: None,
+        };
+        match bot.receive()? {
+            BotMessage::Info { name, version, author, .. } => {
+                bot.name = name;
+                bot.version = version;
+                bot.author = author;
+            },
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "the bot did not introduce itself")),
+        }
+        bot.send(&FrontendMessage::Rules)?;
+        match bot.receive()? {
+            BotMessage::Ready => Ok(bot),
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "the bot is not ready")),
+        }
+    }
+
+    pub fn send(&mut self, message: &FrontendMessage) -> io::Result<()> {
+        let line = serde_json::to_string(message)
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))?;
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()
+    }
+
+    // the next message the frontend understands, others are skipped as the protocol asks
+    pub fn receive(&mut self) -> io::Result<BotMessage> {
+        loop {
+            let line = match self.lines.recv_timeout(BOT_TIMEOUT) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return Err(io::Error::new(ErrorKind::TimedOut, "the bot did not answer")),
+                Err(RecvTimeoutError::Disconnected) => return Err(io::Error::new(ErrorKind::BrokenPipe, "the bot quit")),
+            };
+            match serde_json::from_str(&line) {
+                Ok(BotMessage::Error { reason }) => return Err(io::Error::other(reason)),
+                Ok(message) => return Ok(message),
+                Err(_) => continue,
+            }
+        }
+    }
+
+    pub fn start(&mut self, game: &Game) -> io::Result<()> {
+        let mut queue = vec![game.active_tetrad.name.clone()];
+        queue.extend(game.previewed().map(|tetrad| tetrad.name.clone()));
+        let start = FrontendMessage::Start {
+            hold: game.held_tetrad.clone(),
+            queue,
+            combo: game.combo,
+            back_to_back: game.back_to_back,
+            board: board_rows(&game.board),
+        };
+        self.send(&start)?;
+        self.started = true;
+        self.revealed = previewed(game);
+        Ok(())
+    }
+
+    // tells the bot about tetrads that entered the previews since it last heard
+    fn reveal(&mut self, game: &Game) -> io::Result<()> {
+        let queue = &game.queue;
+        let revealed = previewed(game);
+        for index in self.revealed.max(queue.drawn)..revealed {
+            let piece = queue.tetrads[queue.tetrads.len() - 1 - (index - queue.drawn)].name.clone();
+            self.send(&FrontendMessage::NewPiece { piece })?;
+        }
+        self.revealed = revealed;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        self.started = false;
+        self.send(&FrontendMessage::Stop)
+    }
+}
+
+impl Controller for ExternalBot {
+
+    // asks for suggestions and takes the first one the board allows
+    fn actions(&mut self, game: &Game) -> io::Result<Option<Vec<Action>>> {
+        if game.game_over || game.spawning() {
+            return Ok(None)
+        }
+        if !self.started {
+            self.start(game)?;
+        } else {
+            // tetrads drawn when the last one spawned after a delay
+            self.reveal(game)?;
+        }
+        self.send(&FrontendMessage::Suggest)?;
+        let moves = loop {
+            if let BotMessage::Suggestion { moves } = self.receive()? {
+                break moves
+            }
+        };
+        for play in moves {
+            if let Some(actions) = move_actions(game, &play) {
+                self.pending = Some(Pending {
+                    play,
+                    pieces: game.pieces,
+                    lines: game.lines,
+                    garbage: game.garbage.total(),
+                });
+                return Ok(Some(actions))
+            }
+        }
+        // the tetrad falls on its own and the bot loses track, it starts over from the real board
+        self.stop()?;
+        Ok(None)
+    }
+
+    fn stepped(&mut self, game: &Game) -> io::Result<()> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        if game.pieces != pending.pieces + 1 {
+            // the move did not lock as planned, start over from the real board
+            return self.stop()
+        }
+        self.send(&FrontendMessage::Play { play: pending.play })?;
+        // garbage rises after a lock that clears nothing, which the bot can't foresee
+        let garbage_rose = pending.garbage > 0 && game.lines == pending.lines;
+        if game.game_over || garbage_rose {
+            self.stop()
+        } else {
+            self.reveal(game)
+        }
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let _ = self.send(&FrontendMessage::Quit);
+        for _ in 0..100 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -4,279 +4,70 @@ extern crate ndarray;
 extern crate itertools;
 extern crate termion;
 
-use rand::Rng;
-use rand::prelude::SliceRandom;
-
-use crate::grid::rgb::RGB;
-use crate::grid::grid::colored_char;
-
-use super::tile::{Tile, OUTLINED_SQUARE};
-
-#[derive(Clone, Debug)]
-pub struct Tetrad {
-    pub tiles: [Tile; 4],
-    pub center: (f32, f32),
-    pub render: String,
-    pub name: String,
-}
-
-
-impl Tetrad {
-
-    pub fn new_i() -> Tetrad {
-        let light_blue  = RGB { r: 102, g: 255, b: 255 };
-        let character = colored_char(OUTLINED_SQUARE, light_blue);
-        let mut render = String::new();
-        render.push_str("\n");
-        render.push_str(&character);
-        render.push_str(&character);
-        render.push_str(&character);
-        render.push_str(&character);
-        render.push_str("\n ");
-
-        Tetrad {
-            tiles: [Tile { empty: true, color: light_blue, utf8: OUTLINED_SQUARE, row: 1, column: 3},
-                    Tile { empty: true, color: light_blue, utf8: OUTLINED_SQUARE, row: 1, column: 4},
-                    Tile { empty: true, color: light_blue, utf8: OUTLINED_SQUARE, row: 1, column: 5},
-                    Tile { empty: true, color: light_blue, utf8: OUTLINED_SQUARE, row: 1, column: 6}
-            ],
-            center: (0.0, 5.0),
-            render,
-            name: "I".to_string(),
-        }
-    }
-
-    pub fn new_o() -> Tetrad {
-        let yello  = RGB { r: 255, g: 255, b: 102 };
-        let character = colored_char(OUTLINED_SQUARE, yello);
-        let mut render = String::new();
-        render.push_str("\n");
-        render.push_str(&character);
-        render.push_str(&character);
-        render.push_str("  ");
-        render.push_str("  ");
-        render.push_str("\n");
-        render.push_str(&character);
-        render.push_str(&character);
-
-        Tetrad {
-            tiles: [Tile { empty: true, color: yello, utf8: OUTLINED_SQUARE, row: 0, column: 4},
-                    Tile { empty: true, color: yello, utf8: OUTLINED_SQUARE, row: 1, column: 4},
-                    Tile { empty: true, color: yello, utf8: OUTLINED_SQUARE, row: 1, column: 5},
-                    Tile { empty: true, color: yello, utf8: OUTLINED_SQUARE, row: 0, column: 5}
-            ],
-            center: (0.5, 4.5),
-            render,
-            name: "O".to_string(),
-
-        }
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use super::tile::Tile;
+use super::piece::{Piece, PieceSet};
+use super::randomizer::Randomizer;
+
+// one character per cell: `.` for empty, the standard tetrad's name, or `G` for
+// garbage and anything another piece set locked
+pub fn tile_code(tile: &Tile) -> char {
+    if tile.empty {
+        return '.'
     }
-
-    pub fn new_t() -> Tetrad {
-        let purple  = RGB { r: 178, g: 102, b: 255 };
-        let character = colored_char(OUTLINED_SQUARE, purple);
-        let mut render = String::new();
-        render.push_str("\n");
-        render.push_str("  ");
-        render.push_str(&character);
-        render.push_str("  ");
-        render.push_str("  ");
-        render.push_str("\n");
-        render.push_str(&character);
-        render.push_str(&character);
-        render.push_str(&character);
-
-        Tetrad {
-            tiles: [Tile { empty: true, color: purple, utf8: OUTLINED_SQUARE, row: 1, column: 3},
-                    Tile { empty: true, color: purple, utf8: OUTLINED_SQUARE, row: 1, column: 4},
-                    Tile { empty: true, color: purple, utf8: OUTLINED_SQUARE, row: 1, column: 5},
-                    Tile { empty: true, color: purple, utf8: OUTLINED_SQUARE, row: 0, column: 4}
-            ],
-            center: (1.0, 4.0),
-            render,
-            name: "T".to_string(),
-
+    let color = tile.color;
+    for kind in PieceSet::standard().kinds.iter() {
+        if (kind.color.r, kind.color.g, kind.color.b) == (color.r, color.g, color.b) {
+            return kind.name.chars().next().unwrap()
         }
     }
-
-    pub fn new_s() -> Tetrad {
-        let green  = RGB { r: 102, g: 255, b: 102 };
-        let character = colored_char(OUTLINED_SQUARE, green);
-        let mut render = String::new();
-        render.push_str("\n");
-        render.push_str(&character);
-        render.push_str(&character);
-        render.push_str("  ");
-        render.push_str("  ");
-        render.push_str("\n");
-        render.push_str("  ");
-        render.push_str(&character);
-        render.push_str(&character);
-
-        Tetrad {
-            tiles: [Tile { empty: true, color: green, utf8: OUTLINED_SQUARE, row: 1, column: 5},
-                    Tile { empty: true, color: green, utf8: OUTLINED_SQUARE, row: 1, column: 6},
-                    Tile { empty: true, color: green, utf8: OUTLINED_SQUARE, row: 0, column: 5},
-                    Tile { empty: true, color: green, utf8: OUTLINED_SQUARE, row: 0, column: 4}
-            ],
-            center: (0.0, 5.0),
-            render,
-            name: "S".to_string(),
-
-        }
-    }
-
-    pub fn new_z() -> Tetrad {
-        let red  = RGB { r: 255, g: 0, b: 0 };
-        let character = colored_char(OUTLINED_SQUARE, red);
-        let mut render = String::new();
-        render.push_str("\n");
-        render.push_str("  ");
-        render.push_str(&character);
-        render.push_str(&character);
-        render.push_str("  ");
-        render.push_str("\n");
-        render.push_str(&character);
-        render.push_str(&character);
-
-        Tetrad {
-            tiles: [Tile { empty: true, color: red, utf8: OUTLINED_SQUARE, row: 0, column: 4},
-                    Tile { empty: true, color: red, utf8: OUTLINED_SQUARE, row: 0, column: 5},
-                    Tile { empty: true, color: red, utf8: OUTLINED_SQUARE, row: 1, column: 3},
-                    Tile { empty: true, color: red, utf8: OUTLINED_SQUARE, row: 1, column: 4}
-            ],
-            center: (0.0, 4.0),
-            render,
-            name: "Z".to_string(),
-
-        }
-    }
-
-    pub fn new_j() -> Tetrad {
-        let orange  = RGB { r: 255, g: 153, b: 51 };
-        let character = colored_char(OUTLINED_SQUARE, orange);
-        let mut render = String::new();
-        render.push_str("\n");
-        render.push_str(&character);
-        render.push_str("  ");
-        render.push_str("  ");
-        render.push_str("  ");
-        render.push_str("\n");
-        render.push_str(&character);
-        render.push_str(&character);
-        render.push_str(&character);
-
-        Tetrad {
-            tiles: [Tile { empty: true, color: orange, utf8: OUTLINED_SQUARE, row: 0, column: 3},
-                    Tile { empty: true, color: orange, utf8: OUTLINED_SQUARE, row: 1, column: 4},
-                    Tile { empty: true, color: orange, utf8: OUTLINED_SQUARE, row: 1, column: 5},
-                    Tile { empty: true, color: orange, utf8: OUTLINED_SQUARE, row: 1, column: 3}
-            ],
-            center: (1.0, 4.0),
-            render,
-            name: "J".to_string(),
-
-        }
-    }
-
-    pub fn new_l() -> Tetrad {
-        let dark_blue  = RGB { r: 0, g: 0, b: 255 };
-        let character = colored_char(OUTLINED_SQUARE, dark_blue);
-        let mut render = String::new();
-        render.push_str("\n");
-        render.push_str("  ");
-        render.push_str("  ");
-        render.push_str(&character);
-        render.push_str("  ");
-        render.push_str("\n");
-        render.push_str(&character);
-        render.push_str(&character);
-        render.push_str(&character);
-        //render.push_str("\r");
-
-        Tetrad {
-            tiles: [Tile { empty: true, color: dark_blue, utf8: OUTLINED_SQUARE, row: 0, column: 6},
-                    Tile { empty: true, color: dark_blue, utf8: OUTLINED_SQUARE, row: 1, column: 4},
-                    Tile { empty: true, color: dark_blue, utf8: OUTLINED_SQUARE, row: 1, column: 5},
-                    Tile { empty: true, color: dark_blue, utf8: OUTLINED_SQUARE, row: 1, column: 6}
-            ],
-            center: (1.0, 5.0),
-            render,
-            name: "L".to_string(),
-
-        }
-    }
-
-    pub fn new_random() -> Tetrad {
-        let x = rand::thread_rng().gen_range(0,7);
-        match x {
-            0 => Tetrad::new_i(),
-            1 => Tetrad::new_o(),
-            2 => Tetrad::new_t(),
-            3 => Tetrad::new_s(),
-            4 => Tetrad::new_z(),
-            5 => Tetrad::new_j(),
-            6 => Tetrad::new_l(),
-            _ => Tetrad::new_l(),
-        }
-    }
-
-    pub fn new_by_name(name: &str) -> Tetrad {
-        match name {
-            "I" => Tetrad::new_i(),
-            "O" => Tetrad::new_o(),
-            "T" => Tetrad::new_t(),
-            "S" => Tetrad::new_s(),
-            "Z" => Tetrad::new_z(),
-            "J" => Tetrad::new_j(),
-            "L" => Tetrad::new_l(),
-            _ => Tetrad::new_l(),
-        }
-    }
-
-    pub fn get_position(&self) -> Vec<(usize, usize)> {
-        let mut position: Vec<(usize, usize)> = Vec::new();
-		for tile in self.tiles.iter() {
-			position.push((tile.row, tile.column))
-		}
-		position
-    }
+    'G'
 }
 
-#[derive(Default)]
+// at least this many tetrads wait in the queue after each draw
+pub const QUEUE_LENGTH: usize = 7;
+
+// tetrads are stored in reverse, the next one is last
 pub struct Queue {
-    pub tetrads: Vec<Tetrad>
+    pub tetrads: Vec<Piece>,
+    pub seed: u64,
+    // tetrads taken out of the queue so far
+    pub drawn: usize,
+    pub pieces: PieceSet,
+    names: Vec<String>,
+    rng: StdRng,
+    randomizer: Box<dyn Randomizer>,
 }
 
 impl Queue {
 
-    pub fn new() -> Queue {
-        Queue { tetrads: Queue::new_shuffled_seven() }
+    pub fn new(seed: u64, randomizer: Box<dyn Randomizer>, pieces: PieceSet) -> Queue {
+        let mut queue = Queue {
+            tetrads: Vec::new(),
+            seed,
+            drawn: 0,
+            names: pieces.names(),
+            pieces,
+            rng: StdRng::seed_from_u64(seed),
+            randomizer,
+        };
+        queue.fill();
+        queue
     }
-    
-    pub fn next_tetrad(&mut self) -> Tetrad {
-        match self.tetrads.len() {
-            0 ..= 6 => {
-                self.tetrads.reverse();
-                self.tetrads.append(&mut Queue::new_shuffled_seven());
-                self.tetrads.reverse();
-                self.tetrads.pop().unwrap()
-            },
-            _ => self.tetrads.pop().unwrap() 
-        }
+
+    pub fn next_tetrad(&mut self) -> Piece {
+        let tetrad = self.tetrads.pop().unwrap();
+        self.drawn += 1;
+        self.fill();
+        tetrad
     }
 
-    fn new_shuffled_seven() -> Vec<Tetrad> {
-        let mut rng = rand::thread_rng();
-        let mut queue: Vec<Tetrad> = Vec::new();
-        queue.push(Tetrad::new_i());
-        queue.push(Tetrad::new_o());
-        queue.push(Tetrad::new_t());
-        queue.push(Tetrad::new_s());
-        queue.push(Tetrad::new_z());
-        queue.push(Tetrad::new_j());
-        queue.push(Tetrad::new_l());
-        queue.shuffle(&mut rng);
-        queue
+    fn fill(&mut self) {
+        while self.tetrads.len() < QUEUE_LENGTH {
+            let index = self.randomizer.next_index(&mut self.rng, &self.names);
+            self.tetrads.insert(0, Piece::new(&self.pieces.kinds[index]));
+        }
     }
 }
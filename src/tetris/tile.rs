@@ -4,6 +4,7 @@ extern crate ndarray;
 extern crate itertools;
 extern crate termion;
 
+use std::str::FromStr;
 
 use crate::grid::rgb::RGB;
 use crate::grid::grid::Depict;
@@ -18,8 +19,8 @@ pub struct Tile {
     pub empty: bool,
     pub color: RGB,
     pub utf8: [u8; 4],
-    pub row: usize,
-    pub column: usize,
+    pub row: isize,
+    pub column: isize,
 }
 
 impl Default for Tile {
@@ -36,11 +37,57 @@ impl Default for Tile {
 
 impl Tile {
 
-    pub fn new(row: usize, column: usize) -> Tile {
+    pub fn new(row: isize, column: isize) -> Tile {
         Tile { row, column, ..Default::default() }
     }
 }
 
+// how the landing spot of the falling tetrad is drawn
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Ghost {
+    Outline,
+    // solid squares in a faded color
+    Solid,
+    Off,
+}
+
+impl Ghost {
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Ghost::Outline => "outline",
+            Ghost::Solid => "solid",
+            Ghost::Off => "off",
+        }
+    }
+
+    // a ghost tile as this style draws it, None if it isn't drawn
+    pub fn style(self, tile: Tile) -> Option<Tile> {
+        match self {
+            Ghost::Outline => Some(Tile { utf8: SQUARE_OUTLINE, ..tile }),
+            Ghost::Solid => {
+                let mut color = tile.color;
+                color.mix_color(Tile::default().color, 0.6);
+                Some(Tile { utf8: SQUARE, color, ..tile })
+            },
+            Ghost::Off => None,
+        }
+    }
+}
+
+impl FromStr for Ghost {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Ghost, String> {
+        match name {
+            "outline" => Ok(Ghost::Outline),
+            "solid" => Ok(Ghost::Solid),
+            "off" => Ok(Ghost::Off),
+            _ => Err(format!("unknown ghost style: {}", name)),
+        }
+    }
+}
+
 impl Depict for Tile {
 
     fn color(&self) -> RGB {
@@ -0,0 +1,43 @@
+use super::game::{Action, Game, Settings};
+use super::mode::Mode;
+
+pub const PLAYERS: usize = 2;
+
+// two games stepped in lockstep, every frame each one's attack lands in the other's meter
+pub struct Versus {
+    pub players: [Game; PLAYERS],
+}
+
+impl Versus {
+
+    // both players get the same tetrads and the same garbage holes
+    pub fn new(settings: Settings) -> Versus {
+        let settings = Settings { mode: Mode::Versus, ..settings };
+        Versus { players: [Game::new(settings.clone()), Game::new(settings)] }
+    }
+
+    pub fn step(&mut self, inputs: [&[Action]; PLAYERS]) {
+        if self.finished() {
+            return
+        }
+        for (game, inputs) in self.players.iter_mut().zip(inputs.iter()) {
+            game.step(inputs);
+        }
+        let attacks = [self.players[0].take_attack(), self.players[1].take_attack()];
+        self.players[0].receive_garbage(attacks[1]);
+        self.players[1].receive_garbage(attacks[0]);
+    }
+
+    pub fn finished(&self) -> bool {
+        self.players.iter().any(|game| game.game_over)
+    }
+
+    // the player still standing, None while both are playing or if both topped out on the same frame
+    pub fn winner(&self) -> Option<usize> {
+        match (self.players[0].game_over, self.players[1].game_over) {
+            (false, true) => Some(0),
+            (true, false) => Some(1),
+            _ => None,
+        }
+    }
+}